@@ -122,6 +122,7 @@ mod pta;
 use clap::{App, Arg};
 use glob::glob;
 use pta::{experiments, PTA};
+use rand::thread_rng;
 use std::path::Path;
 use std::time::Instant;
 
@@ -183,6 +184,54 @@ fn main() {
                 .help("Calculate the most probable tree/best run for all pta \
                 in the test set: experiments/pta/test1/."),
         )
+        .arg(
+            Arg::with_name("sample")
+                .short("s")
+                .long("sample")
+                .takes_value(true)
+                .value_name("N")
+                .conflicts_with_all(&["generate", "experiments", "best_parse"])
+                .help("Draw N random trees from the pta's own distribution \
+                and print them along with their probability (fewer than N \
+                if too many draws exceed the depth bound of 100 and are \
+                rejected). A Monte-Carlo \
+                sanity check: the empirical frequency of the most-sampled \
+                tree should approach the probability reported by the most \
+                probable tree algorithm."),
+        )
+        .arg(
+            Arg::with_name("approx")
+                .short("a")
+                .long("approx")
+                .conflicts_with_all(&[
+                    "generate",
+                    "experiments",
+                    "best_parse",
+                    "sample",
+                    "k_best",
+                ])
+                .help("Approximate the most probable tree via stochastic \
+                local search (hill-climbing from the best parse, with \
+                random restarts) instead of the exact best-first search. \
+                Trades exactness for tractability on automata too large \
+                for the exact algorithm to finish on."),
+        )
+        .arg(
+            Arg::with_name("k_best")
+                .short("k")
+                .long("k-best")
+                .takes_value(true)
+                .value_name("N")
+                .conflicts_with_all(&[
+                    "generate",
+                    "experiments",
+                    "best_parse",
+                    "sample",
+                ])
+                .help("Calculate the N most probable trees instead of just \
+                the single best one, printed in non-increasing order of \
+                probability."),
+        )
         .get_matches();
 
     // generate all test pta (with varying amount of level, multiplicity, number
@@ -262,6 +311,65 @@ fn main() {
             }
             println!("time:\t\t {:?}\n", start_time.elapsed());
         }
+    } else if matches.is_present("sample") {
+        let n: usize = matches
+            .value_of("sample")
+            .unwrap()
+            .parse()
+            .expect("--sample expects a non-negative integer");
+        let pta: PTA<String, String> =
+            PTA::from_file(Path::new(&matches.value_of("INPUT").unwrap())).0;
+        if matches.is_present("verbose") {
+            println!("{}", pta);
+        }
+
+        // empirical frequencies of these draws should approach the
+        // probabilities `most_probable_tree` reports
+        let mut rng = thread_rng();
+        for xi in pta.sample_n(&mut rng, n, 100) {
+            let p = pta.probability(&xi);
+            println!("{}\t{}", xi, p);
+        }
+    } else if matches.is_present("approx") {
+        let pta: PTA<String, String> =
+            PTA::from_file(Path::new(&matches.value_of("INPUT").unwrap())).0;
+        if matches.is_present("verbose") {
+            println!("{}", pta);
+        }
+
+        let start_time = Instant::now();
+        let mut rng = thread_rng();
+        let (tree, pr, evaluated) =
+            pta.approximate_most_probable_tree(&mut rng, 10_000, 200, 100);
+        println!("approx mpt:\t {}", tree);
+        println!("probability:\t {}", pr);
+        println!("evaluated:\t {}", evaluated);
+        println!("time:\t\t {:?}", start_time.elapsed());
+    } else if matches.is_present("k_best") {
+        let k: usize = matches
+            .value_of("k_best")
+            .unwrap()
+            .parse()
+            .expect("--k-best expects a non-negative integer");
+        let pta: PTA<String, String> =
+            PTA::from_file(Path::new(&matches.value_of("INPUT").unwrap())).0;
+        if matches.is_present("verbose") {
+            println!("{}", pta);
+        }
+
+        let start_time = Instant::now();
+        match pta.k_most_probable_trees(k, matches.occurrences_of("verbose"))
+        {
+            Ok((trees, insertion_count)) => {
+                for (tree, pr) in &trees {
+                    println!("tree:\t\t {}", tree);
+                    println!("probability:\t {}", pr);
+                }
+                println!("insertions:\t {}", insertion_count);
+            }
+            Err(e) => panic!("{}", e),
+        }
+        println!("time:\t\t {:?}", start_time.elapsed());
     }
     // calculate and output the best parse/most probable tree
     else {
@@ -0,0 +1,87 @@
+//! Parameter estimation for a pta whose topology (states, ranked alphabet,
+//! set of applicable transitions) is already fixed, from a corpus of
+//! observed trees. This fills the gap left by `experiments::generate`,
+//! which only ever assigns random proper probabilities rather than values
+//! learned from data.
+
+use crate::pta::{Transition, Tree, PTA};
+use log_domain::LogDomain;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::hash::Hash;
+use std::str::FromStr;
+
+/// Estimates weights from a corpus where every tree comes with its run,
+/// i.e., the transition used at every node is known. This is the fully-
+/// observed case: since there is no latent state sequence left to guess,
+/// the maximum-likelihood estimate is simply the relative frequency of each
+/// transition among those sharing its source state. `used_transitions` is
+/// the multiset of all transitions fired anywhere in the corpus (duplicates
+/// expected, one entry per occurrence); their `probability` fields are
+/// ignored and overwritten with the estimated ones.
+pub fn estimate_from_runs<Q, T>(
+    root_weight_map: HashMap<Q, LogDomain<f64>>,
+    used_transitions: &[Transition<Q, T>],
+) -> PTA<Q, T>
+where
+    Q: Eq + Hash + Clone,
+    T: Eq + Hash + Clone + Display,
+{
+    // tally occurrences of each distinct (source_state, symbol,
+    // target_states) triple, ignoring the (possibly meaningless) incoming
+    // probability
+    let mut counts: HashMap<(Q, T, Vec<Q>), usize> = HashMap::new();
+    for t in used_transitions {
+        *counts
+            .entry((
+                t.source_state.clone(),
+                t.symbol.clone(),
+                t.target_states.clone(),
+            ))
+            .or_insert(0) += 1;
+    }
+
+    let mut totals_by_source: HashMap<Q, usize> = HashMap::new();
+    for ((q, _, _), &count) in &counts {
+        *totals_by_source.entry(q.clone()).or_insert(0) += count;
+    }
+
+    let transitions_vec: Vec<Transition<Q, T>> = counts
+        .into_iter()
+        .map(|((source_state, symbol, target_states), count)| Transition {
+            probability: LogDomain::new(
+                count as f64 / totals_by_source[&source_state] as f64,
+            )
+            .unwrap(),
+            source_state,
+            symbol,
+            target_states,
+        })
+        .collect();
+
+    PTA::new(root_weight_map, transitions_vec)
+}
+
+/// Estimates weights for an existing topology from a corpus of
+/// *unannotated* trees: the `source_state`/`symbol`/`target_states` triples
+/// of `pta`'s transitions fix which derivations are possible, but the state
+/// a run visits at each node is latent, so relative-frequency counting
+/// alone (as in `estimate_from_runs`) is not enough. This delegates to the
+/// inside–outside EM routine `PTA::train` already implements, returning a
+/// new, independently fitted `PTA` rather than mutating `pta` in place.
+/// Since `PTA` has no `Clone` impl, an independent copy is obtained the same
+/// way `PTA::from_file` does: round-tripping through the pta-file format
+/// that `Display`/`FromStr` already agree on.
+pub fn estimate_from_corpus<Q, T>(
+    pta: &PTA<Q, T>,
+    corpus: &[Tree<T>],
+    iterations: usize,
+) -> PTA<Q, T>
+where
+    Q: Eq + Hash + Clone + Display + FromStr,
+    T: Eq + Hash + Clone + Display + FromStr,
+{
+    let mut estimated: PTA<Q, T> = pta.to_string().parse().unwrap();
+    estimated.train(corpus, iterations);
+    estimated
+}
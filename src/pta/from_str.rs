@@ -5,51 +5,137 @@ use nom::{
     take_while, IResult,
 };
 use std::collections::HashMap;
-use std::fmt::{Debug, Display};
+use std::fmt::{self, Debug, Display};
 use std::hash::Hash;
 use std::str::{from_utf8, FromStr};
 
+/// A single problem found while parsing a pta specification. Unlike a plain
+/// error string, a `ParseError` carries enough position information for an
+/// editor to jump straight to the offending line/column, plus the set of
+/// tokens that would have been accepted there.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// 1-based line number within the parsed string.
+    pub line: usize,
+    /// 1-based column (counted in characters, not bytes) within that line.
+    pub column: usize,
+    /// Human-readable description of what went wrong.
+    pub message: String,
+    /// The tokens that would have been accepted at `line`:`column`.
+    pub expected: Vec<String>,
+}
+
+impl ParseError {
+    fn new(
+        line: usize,
+        column: usize,
+        message: String,
+        expected: Vec<&str>,
+    ) -> ParseError {
+        ParseError {
+            line,
+            column,
+            message,
+            expected: expected.into_iter().map(String::from).collect(),
+        }
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)?;
+        if !self.expected.is_empty() {
+            write!(f, " (expected {})", self.expected.join(" or "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Translates the bytes of `line` that a failing sub-parser has already
+/// consumed (i.e. everything before `remaining`) into a 1-based column.
+fn column_at(line: &str, remaining: &[u8]) -> usize {
+    let consumed = line.as_bytes().len() - remaining.len();
+    from_utf8(&line.as_bytes()[..consumed])
+        .unwrap_or("")
+        .chars()
+        .count()
+        + 1
+}
+
+/// Consumes leading whitespace; unlike the other sub-parsers this can never
+/// fail, so callers may unwrap it unconditionally.
+fn skip_space(input: &[u8]) -> &[u8] {
+    take_while!(input, is_space).unwrap().0
+}
+
 impl<Q, T> FromStr for PTA<Q, T>
 where
     Q: Eq + Hash + Clone + FromStr,
     T: Eq + Hash + Clone + FromStr + Display,
 {
-    type Err = String;
+    type Err = Vec<ParseError>;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mut root_pr = HashMap::new();
         let mut transitions: Vec<Transition<Q, T>> = Vec::new();
+        let mut errors: Vec<ParseError> = Vec::new();
+
+        for (idx, l) in s.lines().enumerate() {
+            let line_no = idx + 1;
+            let indent = l.len() - l.trim_start().len();
+            let trimmed_start = l.trim_start();
+            let trimmed = l.trim();
 
-        for l in s.lines() {
-            if l.trim_start().starts_with("root:") {
-                match parse_root_pr(l.trim_start().as_bytes()) {
+            if trimmed_start.starts_with("root:") {
+                match parse_root_pr(trimmed_start.as_bytes()) {
                     Ok((_, (q, w))) => {
                         if root_pr.insert(q, w).is_some() {
-                            return Err(format!(
-                                "State has multiple root probabilities \
-                                 assigned: {}",
-                                l
+                            errors.push(ParseError::new(
+                                line_no,
+                                indent + 1,
+                                format!(
+                                    "state already has a root probability \
+                                     assigned: {}",
+                                    trimmed
+                                ),
+                                vec![],
                             ));
-                        };
+                        }
                     }
-                    _ => {
-                        return Err(format!(
-                            "Malformed root probability declaration: {}",
-                            l
-                        ));
+                    Err(_) => {
+                        let mut e = diagnose_root_pr::<Q>(trimmed_start);
+                        e.line = line_no;
+                        e.column += indent;
+                        errors.push(e);
+                    }
+                }
+            } else if !trimmed.is_empty() && !trimmed.starts_with('%') {
+                match trimmed.parse::<Transition<Q, T>>() {
+                    Ok(t) => transitions.push(t),
+                    Err(mut e) => {
+                        e.line = line_no;
+                        e.column += indent;
+                        errors.push(e);
                     }
                 }
-            } else if !l.is_empty() && !l.trim_start().starts_with('%') {
-                let t: Transition<Q, T> = l.trim().parse()?;
-                transitions.push(t);
             }
         }
+
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
         match (root_pr, transitions) {
-            (ref r, ref tr) if r.is_empty() || tr.is_empty() => Err(
-                "Incomplete pta definition (root weights and transitions are \
-                 necessary)"
-                    .to_string(),
-            ),
+            (ref r, ref tr) if r.is_empty() || tr.is_empty() => {
+                Err(vec![ParseError::new(
+                    1,
+                    1,
+                    "incomplete pta definition (root weights and \
+                     transitions are necessary)"
+                        .to_string(),
+                    vec![],
+                )])
+            }
             (root_pr, transitions) => Ok(PTA::new(root_pr, transitions)),
         }
     }
@@ -60,16 +146,163 @@ where
     Q: FromStr,
     T: FromStr,
 {
-    type Err = String;
+    type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match parse_transition(s.as_bytes()) {
             Ok((_, result)) => Ok(result),
-            _ => Err(format!("Could not parse: {}", s)),
+            // `line` is a placeholder here: `Transition::from_str` only ever
+            // sees a single, already-isolated line, so the caller (which
+            // knows the line's position within the full pta listing)
+            // overwrites it before surfacing the error.
+            Err(_) => Err(diagnose_transition::<Q, T>(s)),
         }
     }
 }
 
+/// Re-parses a malformed `transition: q0 -> NP(q1, q2) # 0.4` line step by
+/// step, so that the resulting `ParseError` points at the first construct
+/// that failed to match instead of the line as a whole.
+fn diagnose_transition<Q, T>(l: &str) -> ParseError
+where
+    Q: FromStr,
+    T: FromStr,
+{
+    let input = l.as_bytes();
+
+    let remaining = match tag!(input, "transition:") {
+        Ok((r, _)) => r,
+        Err(_) => {
+            return ParseError::new(
+                1,
+                column_at(l, input),
+                "expected a transition declaration".to_string(),
+                vec!["transition:"],
+            );
+        }
+    };
+
+    let remaining = skip_space(remaining);
+    let remaining = match parse_token::<Q>(remaining) {
+        Ok((r, _)) => r,
+        Err(_) => {
+            return ParseError::new(
+                1,
+                column_at(l, remaining),
+                "expected a source state".to_string(),
+                vec!["a state identifier"],
+            );
+        }
+    };
+
+    let remaining = skip_space(remaining);
+    let remaining = match alt!(remaining, tag!("->") | tag!("→")) {
+        Ok((r, _)) => r,
+        Err(_) => {
+            return ParseError::new(
+                1,
+                column_at(l, remaining),
+                "expected an arrow to the symbol".to_string(),
+                vec!["->", "→"],
+            );
+        }
+    };
+
+    let remaining = skip_space(remaining);
+    let remaining = match parse_token::<T>(remaining) {
+        Ok((r, _)) => r,
+        Err(_) => {
+            return ParseError::new(
+                1,
+                column_at(l, remaining),
+                "expected a symbol".to_string(),
+                vec!["a symbol"],
+            );
+        }
+    };
+
+    let remaining = skip_space(remaining);
+    let remaining =
+        match call!(remaining, |x| parse_vec(x, parse_token::<Q>, "(", ")", ",")) {
+            Ok((r, _)) => r,
+            Err(_) => {
+                return ParseError::new(
+                    1,
+                    column_at(l, remaining),
+                    "expected a parenthesised list of target states"
+                        .to_string(),
+                    vec!["(q1, q2, ...)"],
+                );
+            }
+        };
+
+    let remaining = skip_space(remaining);
+    match tag!(remaining, "#") {
+        Ok(_) => ParseError::new(
+            1,
+            column_at(l, remaining),
+            "could not parse the transition weight".to_string(),
+            vec!["a floating point number"],
+        ),
+        Err(_) => ParseError::new(
+            1,
+            column_at(l, remaining),
+            "expected a transition weight".to_string(),
+            vec!["#"],
+        ),
+    }
+}
+
+/// Re-parses a malformed `root: q0 # 0.43` line step by step, so that the
+/// resulting `ParseError` points at the construct that failed to match.
+fn diagnose_root_pr<Q>(l: &str) -> ParseError
+where
+    Q: FromStr,
+{
+    let input = l.as_bytes();
+
+    let remaining = match tag!(input, "root:") {
+        Ok((r, _)) => r,
+        Err(_) => {
+            return ParseError::new(
+                1,
+                column_at(l, input),
+                "expected a root probability declaration".to_string(),
+                vec!["root:"],
+            );
+        }
+    };
+
+    let remaining = skip_space(remaining);
+    let remaining = match parse_token::<Q>(remaining) {
+        Ok((r, _)) => r,
+        Err(_) => {
+            return ParseError::new(
+                1,
+                column_at(l, remaining),
+                "expected a state identifier".to_string(),
+                vec!["a state identifier"],
+            );
+        }
+    };
+
+    let remaining = skip_space(remaining);
+    match tag!(remaining, "#") {
+        Ok(_) => ParseError::new(
+            1,
+            column_at(l, remaining),
+            "could not parse the root probability weight".to_string(),
+            vec!["a floating point number"],
+        ),
+        Err(_) => ParseError::new(
+            1,
+            column_at(l, remaining),
+            "expected a root probability weight".to_string(),
+            vec!["#"],
+        ),
+    }
+}
+
 /// Parses a transition.
 /// A transition has to be of the form `transition: q0 -> NP(q1, q2)`.
 fn parse_transition<Q, T>(input: &[u8]) -> IResult<&[u8], Transition<Q, T>>
@@ -2,6 +2,7 @@
 //! can be recognised by a pta. The most probable tree and best parse algorithms
 //! are part of the pta implementation.
 
+pub mod estimate;
 pub mod experiments;
 mod from_str;
 mod transition;
@@ -9,8 +10,10 @@ mod tree;
 
 use integeriser::{HashIntegeriser, Integeriser};
 use log_domain::LogDomain;
-use num_traits::Zero;
+use nalgebra::{DMatrix, DVector};
+use num_traits::{One, Zero};
 use priority_queue::PriorityQueue;
+use rand::Rng;
 use std::cmp;
 use std::collections::{HashMap, HashSet};
 use std::error::Error;
@@ -19,7 +22,10 @@ use std::fs;
 use std::hash::Hash;
 use std::path::Path;
 use std::str::FromStr;
+use std::time::{Duration, Instant};
+use from_str::ParseError;
 use transition::{Integerisable, Transition};
+pub use tree::parse_checked;
 use tree::Tree;
 
 /// A probabilistic tree automaton A = (Q, Σ, μ, ν).
@@ -42,6 +48,244 @@ where
     transitions: HashMap<usize, HashMap<usize, Vec<Transition<usize, usize>>>>,
 }
 
+/// A complete derivation: a `Tree<T>` with the (integerised) automaton
+/// state recorded at every node. Plain `Tree<T>`s do not carry this
+/// information, so whenever an algorithm needs to know which state a node
+/// came from in order to re-expand it — `best_derivations`/`best_parse`'s
+/// internal search and the local search in `approximate_most_probable_tree`
+/// — it operates on `Derivation<T>` instead and converts to `Tree<T>` only
+/// for the final result.
+#[derive(Clone)]
+struct Derivation<T> {
+    state: usize,
+    symbol: T,
+    children: Vec<Derivation<T>>,
+}
+
+/// A witness for `best_run`: at every node, the `Transition` (by state,
+/// symbol, target states and probability) that the single most probable
+/// derivation of a fixed input tree actually used, together with the
+/// (recursively witnessed) runs used for its children. Unlike `Derivation<T>`
+/// (an internal helper for re-expanding/resampling candidate trees during a
+/// search), `Run<T>` is the public record of *why* a tree scores the way it
+/// does: every transition probability along the way is retained instead of
+/// being folded into a single total.
+#[derive(Clone, Debug)]
+pub struct Run<T> {
+    pub state: usize,
+    pub symbol: T,
+    pub target_states: Vec<usize>,
+    pub probability: LogDomain<f64>,
+    pub children: Vec<Run<T>>,
+}
+
+impl<T: Clone> Run<T> {
+    /// Discards the per-node bookkeeping and returns the plain `Tree<T>`
+    /// that this run derives.
+    pub fn to_tree(&self) -> Tree<T> {
+        Tree::new_with_children(
+            self.symbol.clone(),
+            self.children.iter().map(Run::to_tree).collect(),
+        )
+    }
+}
+
+/// A shared-packed derivation forest over the states of a pta: since a
+/// run's future only depends on the current state (not on how that state
+/// was reached), every run rooted at a given state can be ranked and
+/// reused regardless of context, so `KBestForest` packs one lazily-grown,
+/// probability-descending list of runs per state instead of per subtree.
+/// Extraction follows the lazy k-best algorithm of [Huang and Chiang,
+/// "Better k-best Parsing", 2005]: a per-state `PriorityQueue` of
+/// candidates (an edge together with a rank for each of its target
+/// states) is seeded with every edge's all-zero rank vector, and popping
+/// the `j`-th run from a state pushes the "neighbour" candidates obtained
+/// by advancing exactly one child's rank by one, so only as much of the
+/// forest as `k_best_trees` actually asks for is ever computed. Unlike the
+/// acyclic parse forests the algorithm was designed for, the automaton's
+/// state graph may itself be cyclic (a state reachable from one of its own
+/// transitions); `kth_best`/`expand` guard against this via `seeding` so a
+/// state's own first rank never depends on itself.
+struct KBestForest<T> {
+    edges_by_state: HashMap<usize, Vec<Transition<usize, usize>>>,
+    lists: HashMap<usize, Vec<(Run<T>, LogDomain<f64>)>>,
+    queues: HashMap<usize, PriorityQueue<(usize, Vec<usize>), LogDomain<f64>>>,
+    seen: HashMap<usize, HashSet<(usize, Vec<usize>)>>,
+    /// States whose queue is currently being seeded (see `expand`):
+    /// guards against the automaton's state graph being cyclic, which the
+    /// lazy Huang–Chiang extraction otherwise assumes away.
+    seeding: HashSet<usize>,
+}
+
+impl<T: Clone + Eq + Hash + Display> KBestForest<T> {
+    fn new(edges_by_state: HashMap<usize, Vec<Transition<usize, usize>>>) -> Self {
+        KBestForest {
+            edges_by_state,
+            lists: HashMap::new(),
+            queues: HashMap::new(),
+            seen: HashMap::new(),
+            seeding: HashSet::new(),
+        }
+    }
+
+    /// The `j`-th best (0 = best) `(run, probability)` rooted at `state`,
+    /// expanding the state's candidate queue lazily until that many runs
+    /// have been extracted or no more exist. While `state`'s queue is
+    /// still being seeded (see `expand`), answers only from what is
+    /// already on its list instead of re-entering `expand`: a transition
+    /// sourced at `state` that targets `state` itself (directly or
+    /// through other states) cannot contribute to `state`'s own first
+    /// rank, since that would require a run of `state` that does not
+    /// exist yet. This is what keeps a self-referential state (e.g.
+    /// `1 -> s(1, 2)`) from recursing forever instead of simply excluding
+    /// that candidate from the first round.
+    fn kth_best(
+        &mut self,
+        state: usize,
+        j: usize,
+        t_integeriser: &HashIntegeriser<T>,
+    ) -> Option<(Run<T>, LogDomain<f64>)> {
+        if self.seeding.contains(&state) {
+            return self.lists.get(&state).and_then(|l| l.get(j)).cloned();
+        }
+        while self.lists.get(&state).map_or(0, Vec::len) <= j {
+            if !self.expand(state, t_integeriser) {
+                break;
+            }
+        }
+        self.lists.get(&state).and_then(|l| l.get(j)).cloned()
+    }
+
+    /// Extracts one more run for `state`: on first use, seeds its queue
+    /// with every applicable edge at rank `0`; otherwise pops the best
+    /// remaining candidate, realises it into a `Run<T>` (recursively
+    /// resolving each child via `kth_best`), appends it to `state`'s list,
+    /// and pushes the rank-advanced neighbours of the popped candidate.
+    /// Returns `false` once `state` has no further runs.
+    fn expand(&mut self, state: usize, t_integeriser: &HashIntegeriser<T>) -> bool {
+        if !self.queues.contains_key(&state) {
+            self.seeding.insert(state);
+            let edges =
+                self.edges_by_state.get(&state).cloned().unwrap_or_default();
+            let mut queue = PriorityQueue::new();
+            let mut seen = HashSet::new();
+            for (edge, t) in edges.iter().enumerate() {
+                let ranks = vec![0; t.target_states.len()];
+                if let Some(weight) =
+                    self.candidate_weight(t, &ranks, t_integeriser)
+                {
+                    seen.insert((edge, ranks.clone()));
+                    queue.push((edge, ranks), weight);
+                }
+            }
+            self.seeding.remove(&state);
+            self.queues.insert(state, queue);
+            self.seen.insert(state, seen);
+        }
+
+        let ((edge, ranks), weight) =
+            match self.queues.get_mut(&state).unwrap().pop() {
+                Some(candidate) => candidate,
+                None => return false,
+            };
+
+        let edges = self.edges_by_state.get(&state).cloned().unwrap_or_default();
+        let t = edges[edge].clone();
+        let mut children = Vec::with_capacity(t.target_states.len());
+        for (&q_i, &r_i) in t.target_states.iter().zip(&ranks) {
+            let (run_i, _) = self
+                .kth_best(q_i, r_i, t_integeriser)
+                .expect("candidate_weight already confirmed this rank exists");
+            children.push(run_i);
+        }
+        let run = Run {
+            state,
+            symbol: t_integeriser.find_value(t.symbol).unwrap().clone(),
+            target_states: t.target_states.clone(),
+            probability: t.probability,
+            children,
+        };
+        self.lists.entry(state).or_insert_with(Vec::new).push((run, weight));
+
+        for i in 0..ranks.len() {
+            let mut next_ranks = ranks.clone();
+            next_ranks[i] += 1;
+            let is_new = self
+                .seen
+                .entry(state)
+                .or_insert_with(HashSet::new)
+                .insert((edge, next_ranks.clone()));
+            if is_new {
+                if let Some(next_weight) =
+                    self.candidate_weight(&t, &next_ranks, t_integeriser)
+                {
+                    self.queues
+                        .get_mut(&state)
+                        .unwrap()
+                        .push((edge, next_ranks), next_weight);
+                }
+            }
+        }
+        true
+    }
+
+    /// The total weight that picking `t` with the given per-child `ranks`
+    /// would produce, or `None` if some child does not have that many
+    /// distinct runs.
+    fn candidate_weight(
+        &mut self,
+        t: &Transition<usize, usize>,
+        ranks: &[usize],
+        t_integeriser: &HashIntegeriser<T>,
+    ) -> Option<LogDomain<f64>> {
+        let mut weight = t.probability;
+        for (&q_i, &r_i) in t.target_states.iter().zip(ranks) {
+            let (_, w_i) = self.kth_best(q_i, r_i, t_integeriser)?;
+            weight *= w_i;
+        }
+        Some(weight)
+    }
+}
+
+impl<T: Clone> Derivation<T> {
+    fn to_tree(&self) -> Tree<T> {
+        Tree::new_with_children(
+            self.symbol.clone(),
+            self.children.iter().map(Derivation::to_tree).collect(),
+        )
+    }
+
+    /// Collects the paths (root-to-node child-index sequences, in
+    /// depth-first order; `[]` denotes the root itself) to every node of
+    /// the derivation.
+    fn paths(&self) -> Vec<Vec<usize>> {
+        let mut result = vec![Vec::new()];
+        for (i, child) in self.children.iter().enumerate() {
+            for mut p in child.paths() {
+                p.insert(0, i);
+                result.push(p);
+            }
+        }
+        result
+    }
+
+    /// The state recorded at `path`, descending from the root.
+    fn state_at(&self, path: &[usize]) -> usize {
+        match path.split_first() {
+            Some((&i, rest)) => self.children[i].state_at(rest),
+            None => self.state,
+        }
+    }
+
+    /// Replaces the subtree at `path` with `replacement`.
+    fn replace_at(&mut self, path: &[usize], replacement: Derivation<T>) {
+        match path.split_first() {
+            Some((&i, rest)) => self.children[i].replace_at(rest, replacement),
+            None => *self = replacement,
+        }
+    }
+}
+
 impl<Q, T> PTA<Q, T>
 where
     Q: Eq + Hash + Clone,
@@ -49,7 +293,11 @@ where
 {
     /// Instantiates a new PTA from all non-null root weights and a list of
     /// transitions.
-    /// TODO consistency check
+    /// Note that this does not check whether the result is consistent (i.e.
+    /// defines a proper probability distribution over T_Σ); use
+    /// `is_consistent`/`partition_function` on the constructed automaton if
+    /// that needs to be verified, e.g. after training or hand-editing a pta
+    /// file.
     fn new(
         root_weight_map: HashMap<Q, LogDomain<f64>>,
         transitions_vec: Vec<Transition<Q, T>>,
@@ -118,13 +366,22 @@ where
                 e.description()
             ),
         };
+        let pta: PTA<Q, T> = match pta_string.parse() {
+            Ok(pta) => pta,
+            Err(errors) => panic!(
+                "Could not parse pta file {}:\n{}",
+                path.display(),
+                errors
+                    .iter()
+                    .map(ParseError::to_string)
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            ),
+        };
         if pta_string.starts_with('%') {
-            (
-                pta_string.parse().unwrap(),
-                pta_string.lines().next().unwrap().to_string(),
-            )
+            (pta, pta_string.lines().next().unwrap().to_string())
         } else {
-            (pta_string.parse().unwrap(), "".to_string())
+            (pta, "".to_string())
         }
     }
 
@@ -138,7 +395,16 @@ where
     ) -> Vec<LogDomain<f64>> {
         // get probabilities for tree xi if they have been calculated before
         if known_trees.contains(&xi) {
-            known_trees.get(&xi).unwrap().run.clone()
+            // even on a cache hit, `xi` itself is a distinct tree node (e.g.
+            // the second of two identical sibling subtrees) whose own `run`,
+            // and that of every node below it, has never been set, so
+            // callers reading them afterwards (the E-step in
+            // `accumulate_expected_counts`) must still see them: copy the
+            // cached subtree as a whole, keeping only `xi`'s own `is_prefix`
+            let is_prefix = xi.is_prefix;
+            *xi = known_trees.get(&xi).unwrap().clone();
+            xi.is_prefix = is_prefix;
+            xi.run.clone()
         } else {
             // gather all transitions that have xi.root as a symbol
             let transitions = self
@@ -177,10 +443,12 @@ where
         }
     }
 
-    /// Calculates the probability of a (prefix-)tree ξ ∈ T_Σ(X).
-    /// Base case for the recursive computation done in fn probability_rec and
-    /// applies root weights.
-    fn probability(
+    /// Calculates the probability of a (prefix-)tree ξ ∈ T_Σ(X), given a
+    /// `known_trees` cache shared across many calls (e.g. across an entire
+    /// best-first search). Base case for the recursive computation done in
+    /// fn probability_rec and applies root weights. The public, ergonomic
+    /// entry point for *complete* trees is `probability`.
+    fn inside_probability(
         &self,
         xi: &mut Tree<T>,
         mut known_trees: &mut HashSet<Tree<T>>,
@@ -194,18 +462,96 @@ where
             .sum()
     }
 
-    /// Compute the potential probability PP(ξ) = min(|Q|²/height(ξ), Pr(ξ)).
-    /// This is supposed to take the bound of Theorem X (TODO) into account
-    /// similar to what is done in Definition 2 by de la Higuera and Oncina 2013
-    /// [Definition 2, dlHO13b]. Currently not in use since the bound is not
-    /// tight enough to affect the outcome.
-    fn _potential_probability(
+    /// Computes the total probability the automaton assigns to the complete
+    /// tree ξ, summed over all runs (the inside value at the root):
+    /// ∑_q ν(q) ⋅ inside(ξ)[q]. Unlike `inside_probability`, this is a
+    /// self-contained entry point: it starts from a fresh `known_trees`
+    /// cache, so callers comparing a handful of trees (e.g. verifying that
+    /// `most_probable_tree`'s reported probability equals `probability(mpt)`)
+    /// do not need to manage one themselves.
+    pub fn probability(&self, xi: &Tree<T>) -> LogDomain<f64> {
+        self.inside_probability(&mut xi.clone(), &mut HashSet::new())
+    }
+
+    /// Computes the single most probable run (Viterbi derivation) of ξ,
+    /// i.e. `max_{κ ∈ R(ξ)} Pr(κ)` instead of `probability`'s
+    /// `Σ_{κ ∈ R(ξ)} Pr(κ)`. Useful for inspecting *why* a tree scores the
+    /// way it does: unlike the summed probability, the returned `Run`
+    /// retains the actual transition used at every node.
+    pub fn best_run(&self, xi: &Tree<T>) -> (LogDomain<f64>, Run<T>) {
+        self.best_run_rec(xi)
+            .into_iter()
+            .zip(&self.root_weights)
+            .filter_map(|((p, run), &root_q)| run.map(|run| (p * root_q, run)))
+            .max_by(|(p_1, _), (p_2, _)| p_1.cmp(p_2))
+            .expect("pta does not accept the given tree")
+    }
+
+    /// Computes, for every state q, the probability of the single most
+    /// probable run on ξ ending in q and the `Run` that witnesses it (`None`
+    /// if no transition on ξ.root reaches q). This is `probability_rec`'s
+    /// Viterbi counterpart: the inner `p_q += p_t` accumulation becomes a
+    /// `max_by`, and the winning transition, together with the witnessing
+    /// runs of its children, is recorded alongside the probability instead
+    /// of being summed away.
+    fn best_run_rec(
+        &self,
+        xi: &Tree<T>,
+    ) -> Vec<(LogDomain<f64>, Option<Run<T>>)> {
+        let transitions = self
+            .transitions
+            .get(&self.t_integeriser.find_key(&xi.root).unwrap())
+            .unwrap();
+        let child_best: Vec<Vec<(LogDomain<f64>, Option<Run<T>>)>> =
+            xi.children.iter().map(|c| self.best_run_rec(c)).collect();
+
+        (0..self.number_states)
+            .map(|q| {
+                transitions
+                    .get(&q)
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|t| {
+                        let mut p_t = t.probability;
+                        let mut children =
+                            Vec::with_capacity(t.target_states.len());
+                        for (i, &q_i) in t.target_states.iter().enumerate() {
+                            let (p_i, run_i) = child_best.get(i)?.get(q_i)?;
+                            p_t *= *p_i;
+                            children.push(run_i.clone()?);
+                        }
+                        Some((
+                            p_t,
+                            Run {
+                                state: q,
+                                symbol: xi.root.clone(),
+                                target_states: t.target_states.clone(),
+                                probability: t.probability,
+                                children,
+                            },
+                        ))
+                    })
+                    .max_by(|(p_1, _), (p_2, _)| p_1.cmp(p_2))
+                    .map_or((LogDomain::zero(), None), |(p, run)| (p, Some(run)))
+            })
+            .collect()
+    }
+
+    /// Computes the potential probability PP(ξ) = min(|Q|²/height(ξ), Pr(ξ)),
+    /// an upper bound on the probability of any completion of the
+    /// (prefix-)tree ξ, similar to Definition 2 by de la Higuera and Oncina
+    /// 2013 [Definition 2, dlHO13b]. Used as the priority queue key in
+    /// `k_most_probable_trees_with_count` instead of the plain Pr(ξ); in
+    /// practice the |Q|²/height(ξ) bound is rarely tighter than Pr(ξ) itself,
+    /// but it remains a valid (if not always improving) bound throughout the
+    /// search, since `Tree::extend` never increases `inside_probability`.
+    fn potential_probability(
         &self,
         xi: &mut Tree<T>,
         mut known_trees: &mut HashSet<Tree<T>>,
     ) -> LogDomain<f64> {
         cmp::min(
-            self.probability(xi, &mut known_trees),
+            self.inside_probability(xi, &mut known_trees),
             LogDomain::new(
                 self.number_states.pow(2) as f64 / xi._get_height() as f64,
             )
@@ -213,6 +559,134 @@ where
         )
     }
 
+    /// Evaluates the right-hand side F(z) of the termination-mass
+    /// polynomial system z_q = Σ_{t: source=q} p_t · Π_i z_{target_i}
+    /// together with its Jacobian J(z), J[q][j] = ∂F_q/∂z_j, at `z`. Each
+    /// transition contributes a monomial p_t·Π_i z_{target_i} to F_q, whose
+    /// partial derivative w.r.t. z_j sums, over every occurrence of j among
+    /// the transition's target states, the product of p_t with every
+    /// *other* target state's current value (so a repeated target state,
+    /// e.g. `q -> σ(q', q')`, differentiates to `2·p_t·z_{q'}` rather than
+    /// `p_t·z_{q'}`).
+    fn termination_mass_f_and_jacobian(
+        &self,
+        z: &DVector<f64>,
+    ) -> (DVector<f64>, DMatrix<f64>) {
+        let n = self.number_states;
+        let mut f = DVector::zeros(n);
+        let mut jacobian = DMatrix::zeros(n, n);
+
+        for by_source in self.transitions.values() {
+            for (&q, ts) in by_source {
+                for t in ts {
+                    let weight = Self::as_f64(t.probability);
+                    let product: f64 =
+                        t.target_states.iter().map(|&q_i| z[q_i]).product();
+                    f[q] += weight * product;
+
+                    for (k, &q_k) in t.target_states.iter().enumerate() {
+                        let partial: f64 = t
+                            .target_states
+                            .iter()
+                            .enumerate()
+                            .filter(|&(m, _)| m != k)
+                            .map(|(_, &q_m)| z[q_m])
+                            .product();
+                        jacobian[(q, q_k)] += weight * partial;
+                    }
+                }
+            }
+        }
+        (f, jacobian)
+    }
+
+    /// Computes the per-state termination mass z_q: the probability that a
+    /// run started in state q produces a finite tree. Solves the monotone
+    /// polynomial system z_q = Σ_{t: source=q} p_t · Π_i z_{target_i}, one
+    /// equation per state, via Newton's method for monotone systems (cf.
+    /// Etessami and Yannakakis, "Recursive Markov Chains, Stochastic
+    /// Grammars, and Monotone Systems of Nonlinear Equations", 2009, and
+    /// Esparza, Kiefer and Luttenberger, "Newton's Method for ω-Continuous
+    /// Semirings", 2008): starting from z = 0, repeatedly sets
+    /// z ← z + (I − J(z))⁻¹ (F(z) − z), falling back to the plain Picard
+    /// step z ← F(z) whenever I − J(z) is singular. This converges
+    /// quadratically, unlike the Picard iteration z_q^(n+1) = F_q(z^(n))
+    /// it replaces, which only converges linearly. Iterates until the
+    /// largest per-state change drops below `epsilon` or `MAX_ITERATIONS`
+    /// is exceeded.
+    fn termination_mass(&self, epsilon: f64) -> Vec<LogDomain<f64>> {
+        const MAX_ITERATIONS: usize = 100;
+
+        let n = self.number_states;
+        let identity = DMatrix::<f64>::identity(n, n);
+        let mut z = DVector::<f64>::zeros(n);
+
+        for _ in 0..MAX_ITERATIONS {
+            let (f, jacobian) = self.termination_mass_f_and_jacobian(&z);
+            let z_next = match (&identity - &jacobian).try_inverse() {
+                Some(inv) => &z + inv * (&f - &z),
+                None => f.clone(),
+            };
+
+            let max_change = (&z_next - &z)
+                .iter()
+                .map(|d| d.abs())
+                .fold(0.0_f64, f64::max);
+            z = z_next;
+            if max_change < epsilon {
+                break;
+            }
+        }
+
+        // Newton's method can overshoot a hair below 0 on the final step
+        // for a state whose true termination mass is exactly 0.
+        z.iter().map(|&p| LogDomain::new(p.max(0.0)).unwrap()).collect()
+    }
+
+    /// The inside weight of `state`: the probability that a run started in
+    /// `state` produces a finite tree, i.e. the `state`-th entry of
+    /// `termination_mass`. This is the same monotone polynomial system
+    /// z_q = Σ_{t: source=q} p_t · Π_i z_{target_i} (and the same Newton
+    /// solver) that `partition_function`/`is_consistent` already use, so
+    /// the inside-weight system is solved here rather than duplicated in a
+    /// second routine.
+    pub fn inside_weight(&self, state: usize) -> LogDomain<f64> {
+        self.termination_mass(1e-10)[state]
+    }
+
+    /// Computes the partition function Σ_q ν(q)·z_q: the total probability
+    /// mass the automaton assigns to finite trees. A pta only defines a
+    /// proper probability distribution over T_Σ when this equals 1; mass can
+    /// otherwise "leak" to infinity when expected branching is supercritical
+    /// (e.g. after training or hand-editing transitions).
+    pub fn partition_function(&self) -> LogDomain<f64> {
+        self.termination_mass(1e-10)
+            .iter()
+            .zip(&self.root_weights)
+            .map(|(&z_q, &nu_q)| z_q * nu_q)
+            .sum()
+    }
+
+    /// Returns the deficiency 1 − `partition_function()`, i.e. the
+    /// probability mass that leaks to infinite trees.
+    pub fn deficiency(&self) -> f64 {
+        1.0 - Self::as_f64(self.partition_function())
+    }
+
+    /// Reports whether the automaton is consistent/proper, i.e. whether its
+    /// deficiency is within `epsilon` of 0. Useful for detecting
+    /// inconsistent automata produced by `train`/`optimize_weights` or by
+    /// hand-editing a pta file.
+    pub fn is_consistent(&self, epsilon: f64) -> bool {
+        self.deficiency().abs() < epsilon
+    }
+
+    /// Converts a `LogDomain<f64>` to a plain `f64` probability via its
+    /// `Display` formatting (the crate does not expose a direct accessor).
+    fn as_f64(p: LogDomain<f64>) -> f64 {
+        p.to_string().parse().unwrap()
+    }
+
     /// Calculates the most probable tree.
     /// The algorithm, corresponding analysis and evaluation can be found in
     /// Section X (TODO) of my master's thesis. This is based on an algorithm
@@ -220,19 +694,69 @@ where
     /// ["Computing the Most Probable String with a Probabilistic Finite State
     /// Machine" by de la Higuera and Oncina,
     /// 2013](https://www.aclweb.org/anthology/W13-1801) [dlHO13b, Algorithm 1].
+    /// `verbosity` controls the amount of progress output (cf. the
+    /// `--verbose` flag in `main.rs`): `0` is silent, `2` prints the current
+    /// best complete tree each time a complete tree is popped, `3`
+    /// additionally prints the
+    /// queue size and insertion count after every iteration of the search
+    /// loop.
     pub fn most_probable_tree(
         &self,
+        verbosity: u64,
     ) -> Result<(Tree<T>, LogDomain<f64>, usize), &str> {
-        // priority queue of explored trees ξ ∈ T_Σ(X), sorted w.r.t. Pr(ξ)
+        let (mut best, insertion_count) =
+            self.k_most_probable_trees_with_count(1, verbosity)?;
+        best.pop()
+            .map(|(tree, pr)| (tree, pr, insertion_count))
+            .ok_or("The pta does not accept any tree.")
+    }
+
+    /// Generalizes `most_probable_tree` to the `k` most probable trees
+    /// instead of stopping at the first, giving users an n-best list for
+    /// downstream reranking. Returned in non-increasing order of
+    /// probability (fewer than `k` entries if the automaton does not
+    /// produce that many distinct complete trees), alongside the number of
+    /// trees inserted into the search queue. See `most_probable_tree` for
+    /// the meaning of `verbosity`.
+    pub fn k_most_probable_trees(
+        &self,
+        k: usize,
+        verbosity: u64,
+    ) -> Result<(Vec<(Tree<T>, LogDomain<f64>)>, usize), &str> {
+        let (mut best, insertion_count) =
+            self.k_most_probable_trees_with_count(k, verbosity)?;
+        best.reverse();
+        Ok((best, insertion_count))
+    }
+
+    /// Shared best-first search underlying `most_probable_tree` and
+    /// `k_most_probable_trees`. The priority queue is keyed by
+    /// `potential_probability`, an upper bound on the probability of any
+    /// completion of a (prefix-)tree; since `Tree::extend` never raises it,
+    /// the queue always pops trees in non-increasing order of that bound.
+    /// Every *complete* tree popped is inserted into the k best found so
+    /// far (evicting the worst once there are more than `k`), replacing the
+    /// single `current_prop` pruning threshold of the original single-best
+    /// search with the probability of the current k-th best complete tree
+    /// found so far (`LogDomain::zero()` until that many have been found).
+    /// `best` is kept sorted ascending by probability so the worst of the k
+    /// sits at index 0 for O(1) eviction. A tree is only expanded into its
+    /// children once popped; its (prefix-)probability is already computed
+    /// (and cached in `known_trees`) when its `potential_probability` is
+    /// taken on insertion.
+    fn k_most_probable_trees_with_count(
+        &self,
+        k: usize,
+        verbosity: u64,
+    ) -> Result<(Vec<(Tree<T>, LogDomain<f64>)>, usize), &str> {
+        // priority queue of explored trees ξ ∈ T_Σ(X), sorted w.r.t. PP(ξ)
         let mut q = PriorityQueue::new();
         let mut insertion_count = 0;
         // set of trees whose probability has already been calculated once
         let mut known_trees = HashSet::new();
-        // the best complete tree ξ ∈ T_Σ (no variables) and its Pr in the queue
-        // (this is to prevent exploring prefix trees with a worse Pr than the
-        // current best because extending a tree never improves the probability)
-        let mut current_best;
-        let mut current_prop = LogDomain::zero();
+        // the k best complete trees ξ ∈ T_Σ (no variables) found so far,
+        // ascending by Pr(ξ)
+        let mut best: Vec<(Tree<T>, LogDomain<f64>)> = Vec::with_capacity(k);
 
         // initially fill the queue with trees consisting of one symbol since we
         // cannot start with an empty tree
@@ -240,22 +764,36 @@ where
             let mut xi = Tree::new(sigma.clone());
             // since sigma has a rank of 0, xi is a complete tree/no prefix-tree
             xi.is_prefix = rank != &0;
-            let pr = self.probability(&mut xi, &mut known_trees);
-            q.push(xi, pr);
+            let pp = self.potential_probability(&mut xi, &mut known_trees);
+            q.push(xi, pp);
             insertion_count += 1;
         }
-        // initialise with an arbitrary value (save the overhead of looking for
-        // the current best complete tree consiting of one symbol)
-        current_best = q.peek().unwrap().0.clone();
 
         while !q.is_empty() {
-            let (xi, pr) = q.pop().unwrap();
+            let (mut xi, pp) = q.pop().unwrap();
+            let current_prop = if best.len() == k {
+                best[0].1
+            } else {
+                LogDomain::zero()
+            };
+
+            // nothing left in the queue can beat the k-th best complete tree
+            // found so far (PP(ξ) bounds the probability of every
+            // completion of ξ, and extending a tree can only keep or lower
+            // it)
+            if best.len() == k && pp < current_prop {
+                break;
+            }
 
             // ξ ∈ T_Σ
             if !xi.is_prefix {
-                current_best = xi;
-                current_prop = pr;
-                break;
+                let pr = self.inside_probability(&mut xi, &mut known_trees);
+                Self::insert_into_k_best(&mut best, xi, pr, k);
+                if verbosity >= 2 {
+                    if let Some((tree, tree_pr)) = best.last() {
+                        println!("current best:\t {} ({})", tree, tree_pr);
+                    }
+                }
             }
             // ξ ∉ T_Σ (contains variables, i.e., is a prefix-tree/context)
             else {
@@ -266,43 +804,277 @@ where
                     // replace the first occurence (breadth first) of x in ξ
                     // with σ and return wether it still contains any vaiables x
                     xi_s.is_prefix = xi_s.extend(s, &self.sigma);
-                    let pr_xi_s = self.probability(&mut xi_s, &mut known_trees);
+                    let pp_xi_s =
+                        self.potential_probability(&mut xi_s, &mut known_trees);
 
                     // do not add (prefix-)trees to the queue that are worse
-                    // than the current best complete tree (extending trees can
-                    // only result in the same or worse probability)
-                    if pr_xi_s > current_prop {
-                        // ξ ∈ T_Σ (t_s complete + better than the current best)
-                        if !xi_s.is_prefix {
-                            current_best = xi_s.clone();
-                            current_prop = pr_xi_s;
-                        }
-                        q.push(xi_s, pr_xi_s);
+                    // than the current k-th best complete tree (extending
+                    // trees can only result in the same or worse PP)
+                    if pp_xi_s > current_prop {
+                        q.push(xi_s, pp_xi_s);
                         insertion_count += 1;
-                        // if insertion_count % 1000 == 0 {
-                        //     eprintln!("{} \t {}", insertion_count, q.len());
-                        // }
                         if insertion_count > 2e+7 as usize {
-                            // eprintln!("abort");
                             return Err(
                                 "Maximum number of insertions (20⁷) exceeded. \
-                                 Calculation of most probable tree aborted.",
+                                 Calculation of most probable trees aborted.",
                             );
                         }
                     }
                 }
             }
+
+            if verbosity >= 3 {
+                println!(
+                    "queue size:\t {}, insertions:\t {}",
+                    q.len(),
+                    insertion_count
+                );
+            }
         }
-        Ok((current_best, current_prop, insertion_count))
+        Ok((best, insertion_count))
+    }
+
+    /// Inserts `(tree, pr)` into `best`, which is kept sorted ascending by
+    /// probability, and evicts the worst entry once its length exceeds `k`.
+    fn insert_into_k_best(
+        best: &mut Vec<(Tree<T>, LogDomain<f64>)>,
+        tree: Tree<T>,
+        pr: LogDomain<f64>,
+        k: usize,
+    ) {
+        if k == 0 {
+            return;
+        }
+        let pos = best.iter().position(|(_, p)| *p > pr).unwrap_or(best.len());
+        best.insert(pos, (tree, pr));
+        if best.len() > k {
+            best.remove(0);
+        }
+    }
+
+    /// Returns the `k` best complete *derivations* together with their
+    /// probabilities, in non-increasing order (fewer than `k` entries if
+    /// the automaton does not produce that many). Unlike
+    /// `k_most_probable_trees`, which enumerates candidate trees directly
+    /// via best-first search, this ranks the automaton's states as a
+    /// shared-packed derivation forest (`KBestForest`) and lazily extracts
+    /// the `k` best runs reachable from a root weighted by `ν(q)`: every
+    /// run rooted at a given state is computed (and ranked) only once,
+    /// however many larger trees reuse it, which avoids the duplicated
+    /// work `k_most_probable_trees` can do when many distinct trees share
+    /// large common subtrees. Each returned probability is a single
+    /// derivation's weight, not the tree's total probability summed over
+    /// all derivations (as `most_probable_tree` reports): if a tree has
+    /// several distinct runs, it can appear more than once among the `k`
+    /// results, once per derivation.
+    pub fn k_best_trees(&self, k: usize) -> Vec<(Tree<T>, LogDomain<f64>)> {
+        if k == 0 {
+            return Vec::new();
+        }
+
+        let mut edges_by_state: HashMap<usize, Vec<Transition<usize, usize>>> =
+            HashMap::new();
+        for by_source in self.transitions.values() {
+            for (&q, ts) in by_source {
+                edges_by_state
+                    .entry(q)
+                    .or_insert_with(Vec::new)
+                    .extend(ts.iter().cloned());
+            }
+        }
+        let mut forest = KBestForest::new(edges_by_state);
+
+        // a virtual root with one hyperedge per state q, weighted by
+        // ν(q), ranked with the same lazy-queue technique as every other
+        // state in the forest
+        let mut queue: PriorityQueue<(usize, usize), LogDomain<f64>> =
+            PriorityQueue::new();
+        let mut seen = HashSet::new();
+        for (q, &nu_q) in self.root_weights.iter().enumerate() {
+            if nu_q > LogDomain::zero() {
+                if let Some((_, w)) =
+                    forest.kth_best(q, 0, &self.t_integeriser)
+                {
+                    seen.insert((q, 0_usize));
+                    queue.push((q, 0), nu_q * w);
+                }
+            }
+        }
+
+        let mut result = Vec::with_capacity(k);
+        while result.len() < k {
+            let ((q, rank), weight) = match queue.pop() {
+                Some(candidate) => candidate,
+                None => break,
+            };
+            let (run, _) = forest
+                .kth_best(q, rank, &self.t_integeriser)
+                .expect("queued candidate already confirmed to exist");
+            result.push((run.to_tree(), weight));
+
+            let next_rank = rank + 1;
+            if seen.insert((q, next_rank)) {
+                if let Some((_, w)) =
+                    forest.kth_best(q, next_rank, &self.t_integeriser)
+                {
+                    queue.push((q, next_rank), self.root_weights[q] * w);
+                }
+            }
+        }
+        result
+    }
+
+    /// Anytime variant of `most_probable_tree` for automata where the full
+    /// best-first search may run too long to wait for: runs the same
+    /// search as `k_most_probable_trees_with_count` with k = 1, but
+    /// checks `start.elapsed()` against `budget` and, if given,
+    /// `max_expansions` against the number of trees popped from the queue
+    /// before every iteration. Returns the best complete tree found so
+    /// far together with a flag that is `true` iff the search proved
+    /// optimality by exhausting/pruning the queue on its own, and `false`
+    /// if it merely ran out of budget or expansions first. `Ok(None)` means
+    /// the budget/expansion cap was hit before any complete tree was ever
+    /// popped — not an error, just "no candidate yet"; `Err` is reserved for
+    /// a search that ran to completion and proved the pta accepts no tree
+    /// at all, or that exceeded the maximum number of queue insertions.
+    pub fn most_probable_tree_budgeted(
+        &self,
+        budget: Duration,
+        max_expansions: Option<usize>,
+    ) -> Result<Option<(Tree<T>, LogDomain<f64>, bool)>, &str> {
+        let mut best_seen: Option<(Tree<T>, LogDomain<f64>)> = None;
+        let proven = self.most_probable_tree_streaming(
+            budget,
+            max_expansions,
+            |tree, pr| best_seen = Some((tree.clone(), pr)),
+        )?;
+        match best_seen {
+            Some((tree, pr)) => Ok(Some((tree, pr, proven))),
+            None if proven => Err("The pta does not accept any tree."),
+            None => Ok(None),
+        }
+    }
+
+    /// Streaming core shared by `most_probable_tree_budgeted`: runs the
+    /// same best-first search, calling `on_improved` with the new current
+    /// best complete tree every time it improves, so callers can consume
+    /// progressively better candidates (e.g. printing them, or stopping
+    /// early on their own criterion) instead of waiting for the whole
+    /// search to finish. Returns whether the search proved optimality
+    /// (the queue was exhausted or pruned) rather than stopping because
+    /// `budget` or `max_expansions` was hit first.
+    pub fn most_probable_tree_streaming<F>(
+        &self,
+        budget: Duration,
+        max_expansions: Option<usize>,
+        mut on_improved: F,
+    ) -> Result<bool, &str>
+    where
+        F: FnMut(&Tree<T>, LogDomain<f64>),
+    {
+        let start = Instant::now();
+        let mut q = PriorityQueue::new();
+        let mut insertion_count = 0;
+        let mut expansions = 0;
+        let mut known_trees = HashSet::new();
+        let mut current_prop = LogDomain::zero();
+
+        for (sigma, rank) in &self.sigma {
+            let mut xi = Tree::new(sigma.clone());
+            xi.is_prefix = rank != &0;
+            let pp = self.potential_probability(&mut xi, &mut known_trees);
+            q.push(xi, pp);
+            insertion_count += 1;
+        }
+
+        while !q.is_empty() {
+            if start.elapsed() >= budget {
+                return Ok(false);
+            }
+            if max_expansions.map_or(false, |limit| expansions >= limit) {
+                return Ok(false);
+            }
+
+            let (mut xi, pp) = q.pop().unwrap();
+            expansions += 1;
+
+            // nothing left in the queue can beat the current best complete
+            // tree (cf. `k_most_probable_trees_with_count`)
+            if pp < current_prop {
+                return Ok(true);
+            }
+
+            if !xi.is_prefix {
+                let pr = self.inside_probability(&mut xi, &mut known_trees);
+                if pr > current_prop {
+                    current_prop = pr;
+                    on_improved(&xi, pr);
+                }
+            } else {
+                for s in self.sigma.keys() {
+                    let mut xi_s = xi.clone();
+                    xi_s.is_prefix = xi_s.extend(s, &self.sigma);
+                    let pp_xi_s = self
+                        .potential_probability(&mut xi_s, &mut known_trees);
+
+                    if pp_xi_s > current_prop {
+                        q.push(xi_s, pp_xi_s);
+                        insertion_count += 1;
+                        if insertion_count > 2e+7 as usize {
+                            return Err(
+                                "Maximum number of insertions (2·10⁷) \
+                                 exceeded. Calculation of most probable \
+                                 trees aborted.",
+                            );
+                        }
+                    }
+                }
+            }
+        }
+        Ok(true)
     }
 
     /// Dertermines the best/most probable parse.
     /// Return the corrresponding tree and the run's probability.
     /// This implementation is based on the BestParse algorithm depicted in
     /// Figure 3 of ["Parsing Algorithms based on Tree Automata" by Maletti and
-    /// Satta, 2009](https://www.aclweb.org/anthology/W09-3801)
-    /// [MS09, Figure 3].
+    /// Satta, 2009](https://www.aclweb.org/anthology/W09-3801) [MS09, Figure
+    /// 3], evaluated as a Knuth-style best-first hyperpath search (Knuth's
+    /// generalization of Dijkstra to superior hyperpaths, as used for
+    /// TATOO-style tree-automaton runs) instead of repeatedly recomputing
+    /// the reachable states and rescanning every transition on each outer
+    /// iteration. A `PriorityQueue` of candidate (state, best-achievable-
+    /// probability) pairs is driven by a `finalized` set: the highest-
+    /// probability candidate is popped and finalized, and only the
+    /// transitions that have it as a child are relaxed. Because every
+    /// weight lies in [0, 1], products of them are monotonically
+    /// non-increasing, so a popped state's probability can never later be
+    /// improved upon — finalizing states in pop order is therefore correct.
     pub fn best_parse(&self) -> (Tree<T>, LogDomain<f64>) {
+        let (best_probabilities, best_derivations) = self.best_derivations();
+
+        // apply root weights and return the (tree, probability)-pair with
+        // maximal probability
+        best_probabilities
+            .iter()
+            .zip(&self.root_weights)
+            .map(|(&p, &root_p)| p * root_p)
+            .zip(best_derivations)
+            .max_by(|(p_1, _), (p_2, _)| p_1.cmp(p_2))
+            .map(|(p, d)| (d.unwrap().to_tree(), p))
+            .unwrap()
+    }
+
+    /// The best-first search underlying `best_parse`, stopping short of
+    /// applying root weights/picking a winning state so that
+    /// `approximate_most_probable_tree` can also seed its local search from
+    /// the per-state optimum. Returns, for every state q, the
+    /// best-achievable probability of a run ending in q and the
+    /// `Derivation` that achieves it (state-tagged so a node can later be
+    /// re-expanded from the right point in μ).
+    fn best_derivations(
+        &self,
+    ) -> (Vec<LogDomain<f64>>, Vec<Option<Derivation<T>>>) {
         // flatten HashMaps, gather all transitions in one vector
         let transitions: Vec<Transition<usize, usize>> = self
             .transitions
@@ -315,105 +1087,760 @@ where
             .flatten()
             .collect();
 
-        // get all root states (states with non-null root weight)
-        let root_states: HashSet<usize> = self
-            .root_weights
-            .iter()
-            .enumerate()
-            .filter(|(_, &p)| p != LogDomain::zero())
-            .map(|(q, _)| q)
-            .collect();
+        // reverse index: child state -> indices of transitions that have it
+        // as a target, so finalizing a state only relaxes the (few)
+        // transitions it can actually affect
+        let mut transitions_by_child: HashMap<usize, Vec<usize>> =
+            HashMap::new();
+        for (i, t) in transitions.iter().enumerate() {
+            for &q_i in &t.target_states {
+                transitions_by_child
+                    .entry(q_i)
+                    .or_insert_with(Vec::new)
+                    .push(i);
+            }
+        }
 
-        // set of states available for application in new transitions
-        let mut explored_states: HashSet<usize> = HashSet::new();
-        // probabilities that can be achieved for a run that ends in given state
+        // probabilities/derivations that can be achieved for a run ending in
+        // a given state
         let mut best_probabilities: Vec<LogDomain<f64>> =
             vec![LogDomain::zero(); self.number_states];
-        // best trees that can be obtained for a run that ends in given state
-        let mut best_trees: Vec<Option<Tree<T>>> =
+        let mut best_derivations: Vec<Option<Derivation<T>>> =
             vec![None; self.number_states];
+        // states whose best-achievable probability is proven optimal
+        let mut finalized: HashSet<usize> = HashSet::new();
+        let mut queue: PriorityQueue<usize, LogDomain<f64>> =
+            PriorityQueue::new();
+
+        // seed the queue with every nullary transition's source state
+        for t in transitions.iter().filter(|t| t.target_states.is_empty()) {
+            self.relax_transition(
+                t,
+                &mut best_probabilities,
+                &mut best_derivations,
+                &mut queue,
+            );
+        }
+
+        while let Some((q, _)) = queue.pop() {
+            if finalized.contains(&q) {
+                continue;
+            }
+            finalized.insert(q);
+
+            if let Some(indices) = transitions_by_child.get(&q) {
+                for &i in indices {
+                    let t = &transitions[i];
+                    if t.target_states.iter().all(|q_i| finalized.contains(q_i))
+                    {
+                        self.relax_transition(
+                            t,
+                            &mut best_probabilities,
+                            &mut best_derivations,
+                            &mut queue,
+                        );
+                    }
+                }
+            }
+        }
+
+        (best_probabilities, best_derivations)
+    }
+
+    /// Relaxes a single transition `t` for `best_derivations`: if applying
+    /// `t` (given the currently best-achievable probability for each of its
+    /// target states) improves on the current best-achievable probability
+    /// for `t.source_state`, records the new probability/derivation and
+    /// (re-)queues the source state at that priority.
+    fn relax_transition(
+        &self,
+        t: &Transition<usize, usize>,
+        best_probabilities: &mut [LogDomain<f64>],
+        best_derivations: &mut [Option<Derivation<T>>],
+        queue: &mut PriorityQueue<usize, LogDomain<f64>>,
+    ) {
+        let pr = t.probability
+            * t.target_states
+                .iter()
+                .map(|&q_i| best_probabilities[q_i])
+                .product::<LogDomain<f64>>();
+
+        if pr > best_probabilities[t.source_state] {
+            best_probabilities[t.source_state] = pr;
+            best_derivations[t.source_state] = Some(Derivation {
+                state: t.source_state,
+                symbol: self.t_integeriser.find_value(t.symbol).unwrap().clone(),
+                children: t
+                    .target_states
+                    .iter()
+                    .map(|&q_i| best_derivations[q_i].clone().unwrap())
+                    .collect(),
+            });
+            queue.push(t.source_state, pr);
+        }
+    }
+
+    /// Re-estimates all transition probabilities and root weights from a
+    /// corpus of un-annotated trees via inside–outside EM, so weights no
+    /// longer have to be hand-specified in the input file. Since the state
+    /// a run visits at each node is latent, a single pass of relative
+    /// frequency counting (as one would do for a fully observed sample) is
+    /// not enough; instead each of up to `iterations` passes re-estimates the
+    /// expected number of times each transition/root state is used and
+    /// renormalises onto those expectations.
+    ///
+    /// For each training tree ξ, `probability_rec` already gives the inside
+    /// values β_q(node) at every node (as a side effect it stores them in
+    /// `node.run`) and Z(ξ) = ∑_q ν(q)·β_q(ε) is its total probability. The
+    /// outside values α are then computed top-down starting from
+    /// α_q(ε) = ν(q), and the expected count of a transition t at a node is
+    /// α_{source}(node)·p_t·∏_i β_{target_i}(child_i)/Z(ξ), accumulated over
+    /// all matching nodes and all trees (the E-step). The M-step
+    /// renormalises the accumulated counts per source state/the root counts
+    /// so outgoing probabilities sum to 1. Training stops once the
+    /// corpus-wide probability (a monotone stand-in for the log-likelihood,
+    /// since log is monotonic) stops improving, or after `iterations` passes.
+    /// Trees with Z(ξ) = 0 (unparseable under the current transitions) are
+    /// skipped with a warning.
+    pub fn train(&mut self, corpus: &[Tree<T>], iterations: usize) {
+        let mut prev_total_probability = LogDomain::zero();
 
-        // apply transitions until all root states are explored
-        while !root_states.is_subset(&explored_states) {
-            // set of states that are not yet explored but can be in one step
-            let reachable_states: HashSet<usize> = transitions
+        for _ in 0..iterations {
+            // expected counts accumulated over the whole corpus, mirroring
+            // the shape of `self.transitions` so the M-step can zip them up
+            let mut transition_counts: HashMap<
+                usize,
+                HashMap<usize, Vec<LogDomain<f64>>>,
+            > = self
+                .transitions
                 .iter()
-                .filter(|t| {
-                    !explored_states.contains(&t.source_state)
-                        && t.target_states
+                .map(|(&symbol, by_source)| {
+                    (
+                        symbol,
+                        by_source
                             .iter()
-                            .cloned()
-                            .collect::<HashSet<usize>>()
-                            .is_subset(&explored_states)
+                            .map(|(&q, ts)| {
+                                (q, vec![LogDomain::zero(); ts.len()])
+                            })
+                            .collect(),
+                    )
                 })
-                .map(|t| t.source_state)
                 .collect();
+            let mut root_counts = vec![LogDomain::zero(); self.number_states];
+            let mut total_probability = LogDomain::one();
+
+            for xi in corpus {
+                let mut xi = xi.clone();
+                let z = self.inside_probability(&mut xi, &mut HashSet::new());
+
+                if z == LogDomain::zero() {
+                    eprintln!(
+                        "[Warning] Skipping unparseable training tree {} \
+                         (Z(ξ) = 0).",
+                        xi
+                    );
+                    continue;
+                }
+
+                total_probability *= z;
+                self.accumulate_expected_counts(
+                    &xi,
+                    &self.root_weights.clone(),
+                    z,
+                    true,
+                    &mut transition_counts,
+                    &mut root_counts,
+                );
+            }
 
-            for q in &reachable_states {
-                let mut best_probabilities_max = LogDomain::zero();
-                // determine the transition that yields the best probability for
-                // a state (go through all transitions whose child states are
-                // explored but whose source state is not)
-                for t in transitions.iter().filter(|t| {
-                    t.target_states
+            // M-step: renormalise expected counts into proper probabilities.
+            // A source state's outgoing mass is shared across all symbols
+            // it transitions under (mirroring `project_transitions_to_simplex`
+            // and `estimate_from_runs`'s `totals_by_source`), so the total is
+            // accumulated per source state across symbols, not per symbol.
+            let mut totals_by_source: HashMap<usize, LogDomain<f64>> =
+                HashMap::new();
+            for by_source in transition_counts.values() {
+                for (&q, counts) in by_source {
+                    let sum: LogDomain<f64> = counts.iter().cloned().sum();
+                    *totals_by_source
+                        .entry(q)
+                        .or_insert_with(LogDomain::zero) += sum;
+                }
+            }
+            for (symbol, by_source) in self.transitions.iter_mut() {
+                for (q, ts) in by_source.iter_mut() {
+                    let counts = &transition_counts[symbol][q];
+                    let total = totals_by_source[q];
+                    if total == LogDomain::zero() {
+                        continue;
+                    }
+                    for (t, &count) in ts.iter_mut().zip(counts) {
+                        t.probability = count / total;
+                    }
+                }
+            }
+            let root_total: LogDomain<f64> =
+                root_counts.iter().cloned().sum();
+            if root_total != LogDomain::zero() {
+                self.root_weights = root_counts
+                    .into_iter()
+                    .map(|count| count / root_total)
+                    .collect();
+            }
+
+            if total_probability <= prev_total_probability {
+                break;
+            }
+            prev_total_probability = total_probability;
+        }
+    }
+
+    /// Accumulates the expected transition/root counts (the E-step of
+    /// `train`) for a single training tree ξ. `alpha` holds the outside
+    /// values α_q(node) for `node` (at the root this is `ν`); `z` is Z(ξ).
+    /// For every state q with α_q(node) ≠ 0 and every transition t with
+    /// `source_state == q` applicable at `node` (i.e. `symbol == node.root`),
+    /// the expected count of t is accumulated and the outside values of
+    /// `node`'s children are updated before recursing into them.
+    fn accumulate_expected_counts(
+        &self,
+        node: &Tree<T>,
+        alpha: &[LogDomain<f64>],
+        z: LogDomain<f64>,
+        is_root: bool,
+        transition_counts: &mut HashMap<
+            usize,
+            HashMap<usize, Vec<LogDomain<f64>>>,
+        >,
+        root_counts: &mut [LogDomain<f64>],
+    ) {
+        if is_root {
+            for (q, &alpha_q) in alpha.iter().enumerate() {
+                if alpha_q != LogDomain::zero() {
+                    root_counts[q] += alpha_q * node.run[q] / z;
+                }
+            }
+        }
+
+        let symbol = self.t_integeriser.find_key(&node.root).unwrap();
+        let transitions = match self.transitions.get(&symbol) {
+            Some(transitions) => transitions,
+            None => return,
+        };
+
+        // outside values for each child, accumulated over every (q, t) pair
+        // applicable at this node
+        let mut child_alpha =
+            vec![vec![LogDomain::zero(); self.number_states]; node.children.len()];
+
+        for (q, &alpha_q) in alpha.iter().enumerate() {
+            if alpha_q == LogDomain::zero() {
+                continue;
+            }
+            let ts = match transitions.get(&q) {
+                Some(ts) => ts,
+                None => continue,
+            };
+            for (j, t) in ts.iter().enumerate() {
+                // ∏_i β_{target_i}(child_i)
+                let beta_product: LogDomain<f64> = t
+                    .target_states
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &q_i)| node.children[i].run[q_i])
+                    .product();
+
+                transition_counts.get_mut(&symbol).unwrap().get_mut(&q).unwrap()
+                    [j] += alpha_q * t.probability * beta_product / z;
+
+                // push the outside values down to each child, leaving out
+                // the child's own inside value (∏_{j≠i} β_{target_j}(...))
+                for (i, &q_i) in t.target_states.iter().enumerate() {
+                    let others: LogDomain<f64> = t
+                        .target_states
                         .iter()
-                        .cloned()
-                        .collect::<HashSet<usize>>()
-                        .is_subset(&explored_states)
-                        && t.source_state == *q
-                }) {
-                    // calculate the probability of applying transition t given
-                    // probabilities for each child state
-                    let pr = t.probability
-                        * t.target_states
+                        .enumerate()
+                        .filter(|&(k, _)| k != i)
+                        .map(|(k, &q_k)| node.children[k].run[q_k])
+                        .product();
+                    child_alpha[i][q_i] += alpha_q * t.probability * others;
+                }
+            }
+        }
+
+        for (child, alpha_child) in
+            node.children.iter().zip(&child_alpha)
+        {
+            self.accumulate_expected_counts(
+                child,
+                alpha_child,
+                z,
+                false,
+                transition_counts,
+                root_counts,
+            );
+        }
+    }
+
+    /// Offers an alternative to `train` that directly maximizes corpus
+    /// log-likelihood by SPSA (simultaneous-perturbation stochastic
+    /// approximation), mirroring perturbation-based hill climbing: useful
+    /// when EM gets stuck, since it only needs forward probability
+    /// evaluations (`probability`, already implemented), not a closed-form
+    /// E/M-step. The vector θ of all transition probabilities (grouped by
+    /// source state, as `train`'s M-step already groups them) is treated as
+    /// the parameters and L(θ) = Σ log Pr(ξ) as the objective. Each step
+    /// draws a random direction Δ of independent ±1 Bernoulli components,
+    /// evaluates L(θ + c_k·Δ) and L(θ − c_k·Δ), forms the simultaneous-
+    /// perturbation gradient estimate ĝ_i = (L⁺ − L⁻)/(2·c_k·Δ_i), and
+    /// updates θ ← θ + a_k·ĝ with decaying gain sequences a_k, c_k. After
+    /// every step, each source state's outgoing probabilities are projected
+    /// back onto the probability simplex (negatives are clamped to a tiny
+    /// floor and renormalized) so the automaton stays valid throughout, and
+    /// the best-likelihood weights seen are what's kept at the end.
+    pub fn optimize_weights<R: Rng>(
+        &mut self,
+        rng: &mut R,
+        corpus: &[Tree<T>],
+        iterations: usize,
+    ) {
+        let mut best_log_likelihood = self.corpus_log_likelihood(corpus);
+        let mut best_transitions = self.transitions.clone();
+
+        for k in 1..=iterations {
+            let a_k = 1.0 / (k as f64 + 10.0);
+            let c_k = 1.0 / (k as f64).powf(1.0 / 6.0);
+
+            // independent ±1 Bernoulli perturbation direction, one
+            // component per transition
+            let directions: HashMap<usize, HashMap<usize, Vec<f64>>> = self
+                .transitions
+                .iter()
+                .map(|(&symbol, by_source)| {
+                    (
+                        symbol,
+                        by_source
                             .iter()
-                            .map(|q_i| best_probabilities[*q_i])
-                            .product();
-                    // determine the best reachable probability
-                    if pr > best_probabilities_max {
-                        best_probabilities_max = pr;
-                        // construct the corresponding tree
-                        best_trees[*q] = Some(Tree::new_with_children(
-                            self.t_integeriser
-                                .find_value(t.symbol)
-                                .unwrap()
-                                .clone(),
-                            t.target_states
-                                .iter()
-                                .map(|q_i| best_trees[*q_i].clone().unwrap())
-                                .collect(),
-                        ));
+                            .map(|(&q, ts)| {
+                                (
+                                    q,
+                                    (0..ts.len())
+                                        .map(|_| {
+                                            if rng.gen::<bool>() {
+                                                1.0
+                                            } else {
+                                                -1.0
+                                            }
+                                        })
+                                        .collect(),
+                                )
+                            })
+                            .collect(),
+                    )
+                })
+                .collect();
+
+            let original = self.transitions.clone();
+            Self::shift_and_project(&mut self.transitions, &directions, c_k);
+            let l_plus = self.corpus_log_likelihood(corpus);
+            self.transitions = original.clone();
+            Self::shift_and_project(&mut self.transitions, &directions, -c_k);
+            let l_minus = self.corpus_log_likelihood(corpus);
+            self.transitions = original;
+
+            for (symbol, by_source) in self.transitions.iter_mut() {
+                for (q, ts) in by_source.iter_mut() {
+                    let delta = &directions[symbol][q];
+                    for (i, t) in ts.iter_mut().enumerate() {
+                        let gradient =
+                            (l_plus - l_minus) / (2.0 * c_k * delta[i]);
+                        let shifted =
+                            Self::as_f64(t.probability) + a_k * gradient;
+                        t.probability = Self::clamp_probability(shifted);
                     }
                 }
-                best_probabilities[*q] = best_probabilities_max;
-                // add only the state to the set of explored states with the
-                // best probability among all unexplored states
-                explored_states.insert(
-                    *reachable_states
-                        .iter()
-                        .max_by(|&q_1, &q_2| {
-                            best_probabilities[*q_1]
-                                .cmp(&best_probabilities[*q_2])
-                        })
-                        .unwrap(),
-                );
+            }
+            Self::project_transitions_to_simplex(&mut self.transitions);
+
+            let log_likelihood = self.corpus_log_likelihood(corpus);
+            if log_likelihood > best_log_likelihood {
+                best_log_likelihood = log_likelihood;
+                best_transitions = self.transitions.clone();
+            }
+        }
+
+        self.transitions = best_transitions;
+    }
+
+    /// Shifts every transition probability by `c` times its perturbation
+    /// direction, then re-projects onto the probability simplex. Used by
+    /// `optimize_weights` to evaluate L(θ + c_k·Δ) (`c` positive) and
+    /// L(θ − c_k·Δ) (`c` negative).
+    fn shift_and_project(
+        transitions: &mut HashMap<
+            usize,
+            HashMap<usize, Vec<Transition<usize, usize>>>,
+        >,
+        directions: &HashMap<usize, HashMap<usize, Vec<f64>>>,
+        c: f64,
+    ) {
+        for (symbol, by_source) in transitions.iter_mut() {
+            for (q, ts) in by_source.iter_mut() {
+                let delta = &directions[symbol][q];
+                for (i, t) in ts.iter_mut().enumerate() {
+                    let shifted = Self::as_f64(t.probability) + c * delta[i];
+                    t.probability = Self::clamp_probability(shifted);
+                }
             }
         }
+        Self::project_transitions_to_simplex(transitions);
+    }
 
-        // apply root weights
-        best_probabilities = best_probabilities
+    /// Re-normalizes every source state's outgoing transition probabilities
+    /// (across all symbols, mirroring how `train`'s M-step groups expected
+    /// counts by source state) so they sum to 1 again.
+    fn project_transitions_to_simplex(
+        transitions: &mut HashMap<
+            usize,
+            HashMap<usize, Vec<Transition<usize, usize>>>,
+        >,
+    ) {
+        let mut totals: HashMap<usize, LogDomain<f64>> = HashMap::new();
+        for by_source in transitions.values() {
+            for (&q, ts) in by_source {
+                let sum: LogDomain<f64> =
+                    ts.iter().map(|t| t.probability).sum();
+                *totals.entry(q).or_insert_with(LogDomain::zero) += sum;
+            }
+        }
+        for by_source in transitions.values_mut() {
+            for (q, ts) in by_source.iter_mut() {
+                let total = totals[q];
+                if total != LogDomain::zero() {
+                    for t in ts.iter_mut() {
+                        t.probability /= total;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Clamps a perturbed/updated probability to a tiny positive floor (so
+    /// it stays a valid `LogDomain`) before simplex renormalization.
+    fn clamp_probability(p: f64) -> LogDomain<f64> {
+        const FLOOR: f64 = 1e-9;
+        LogDomain::new(p.max(FLOOR).min(1.0)).unwrap()
+    }
+
+    /// Computes Σ log Pr(ξ) over the corpus under the automaton's current
+    /// weights, the objective `optimize_weights` maximizes.
+    fn corpus_log_likelihood(&self, corpus: &[Tree<T>]) -> f64 {
+        corpus
             .iter()
-            .zip(&self.root_weights)
-            .map(|(q, p)| *q * *p)
-            .collect::<Vec<LogDomain<f64>>>();
+            .map(|xi| {
+                let mut xi = xi.clone();
+                Self::as_f64(self.inside_probability(&mut xi, &mut HashSet::new()))
+                    .ln()
+            })
+            .sum()
+    }
 
-        // return (tree, probability)-pair with maximal probability
-        best_probabilities
+    /// Draws one random tree according to the automaton's own distribution,
+    /// so users can generate synthetic corpora or Monte-Carlo-estimate
+    /// quantities the exact most-probable-tree search cannot reach. A start
+    /// state is sampled from `root_weights`, and at each visited state one
+    /// of its outgoing transitions is sampled proportional to its
+    /// probability. Since a state's outgoing weights form a branching
+    /// process that may be supercritical, the recursion is bounded by
+    /// `max_depth`: once exceeded, the whole draw is rejected (`None`) so
+    /// the caller can retry, rather than looping forever.
+    pub fn sample<R: Rng>(
+        &self,
+        rng: &mut R,
+        max_depth: usize,
+    ) -> Option<Tree<T>> {
+        let total_root_weight: LogDomain<f64> =
+            self.root_weights.iter().cloned().sum();
+        let q = Self::sample_categorical(
+            rng,
+            self.root_weights.iter().cloned().enumerate(),
+            total_root_weight,
+        )?;
+        self.sample_state(rng, q, max_depth)
+    }
+
+    /// Draws up to `n` trees from the distribution (see `sample`), retrying
+    /// rejected (too-deep) draws so that a supercritical automaton still
+    /// returns a (possibly short) list instead of looping forever; only
+    /// successfully-terminated trees are kept.
+    pub fn sample_n<R: Rng>(
+        &self,
+        rng: &mut R,
+        n: usize,
+        max_depth: usize,
+    ) -> Vec<Tree<T>> {
+        let mut trees = Vec::with_capacity(n);
+        let mut attempts = 0;
+        while trees.len() < n && attempts < n * 100 + 100 {
+            attempts += 1;
+            if let Some(tree) = self.sample(rng, max_depth) {
+                trees.push(tree);
+            }
+        }
+        trees
+    }
+
+    /// Recursively samples a complete subtree for a run that starts in
+    /// state `q`, consuming one level of `depth_remaining` per recursion
+    /// step.
+    fn sample_state<R: Rng>(
+        &self,
+        rng: &mut R,
+        q: usize,
+        depth_remaining: usize,
+    ) -> Option<Tree<T>> {
+        if depth_remaining == 0 {
+            return None;
+        }
+
+        // gather all transitions with q as a source state, across all
+        // symbols
+        let candidates: Vec<&Transition<usize, usize>> = self
+            .transitions
+            .values()
+            .filter_map(|by_source| by_source.get(&q))
+            .flatten()
+            .collect();
+        let total_weight: LogDomain<f64> =
+            candidates.iter().map(|t| t.probability).sum();
+        let index = Self::sample_categorical(
+            rng,
+            candidates.iter().map(|t| t.probability).enumerate(),
+            total_weight,
+        )?;
+        let t = candidates[index];
+
+        let mut children = Vec::with_capacity(t.target_states.len());
+        for &q_i in &t.target_states {
+            children.push(self.sample_state(rng, q_i, depth_remaining - 1)?);
+        }
+        Some(Tree::new_with_children(
+            self.t_integeriser.find_value(t.symbol).unwrap().clone(),
+            children,
+        ))
+    }
+
+    /// Samples an index from `items` (index, weight) pairs proportional to
+    /// weight, given their precomputed `total_weight`. Returns `None` if
+    /// `total_weight` is zero (no applicable choice).
+    fn sample_categorical<R: Rng>(
+        rng: &mut R,
+        items: impl Iterator<Item = (usize, LogDomain<f64>)>,
+        total_weight: LogDomain<f64>,
+    ) -> Option<usize> {
+        if total_weight == LogDomain::zero() {
+            return None;
+        }
+        let threshold = LogDomain::new(rng.gen::<f64>()).unwrap() * total_weight;
+        let mut cumulative = LogDomain::zero();
+        for (index, weight) in items {
+            cumulative += weight;
+            if cumulative >= threshold {
+                return Some(index);
+            }
+        }
+        None
+    }
+
+    /// Same traversal as `sample_state`, but tags every node with the state
+    /// it was drawn for, producing a `Derivation` instead of a bare `Tree`.
+    /// Used to seed/restart the local search in
+    /// `approximate_most_probable_tree`.
+    fn sample_derivation<R: Rng>(
+        &self,
+        rng: &mut R,
+        q: usize,
+        depth_remaining: usize,
+    ) -> Option<Derivation<T>> {
+        if depth_remaining == 0 {
+            return None;
+        }
+
+        let candidates: Vec<&Transition<usize, usize>> = self
+            .transitions
+            .values()
+            .filter_map(|by_source| by_source.get(&q))
+            .flatten()
+            .collect();
+        let total_weight: LogDomain<f64> =
+            candidates.iter().map(|t| t.probability).sum();
+        let index = Self::sample_categorical(
+            rng,
+            candidates.iter().map(|t| t.probability).enumerate(),
+            total_weight,
+        )?;
+        let t = candidates[index];
+
+        let mut children = Vec::with_capacity(t.target_states.len());
+        for &q_i in &t.target_states {
+            children.push(self.sample_derivation(rng, q_i, depth_remaining - 1)?);
+        }
+        Some(Derivation {
+            state: q,
+            symbol: self.t_integeriser.find_value(t.symbol).unwrap().clone(),
+            children,
+        })
+    }
+
+    /// Greedily completes a derivation rooted at state `q` by repeatedly
+    /// picking the single highest-probability applicable transition
+    /// (ignoring the probability its children will in turn accumulate), the
+    /// way `approximate_most_probable_tree` cheaply re-fills the children of
+    /// a node it just re-expanded. Bounded by `depth_remaining` the same way
+    /// `sample_state` is, to reject (`None`) derivations that do not
+    /// terminate within the budget.
+    fn greedy_derivation(
+        &self,
+        q: usize,
+        depth_remaining: usize,
+    ) -> Option<Derivation<T>> {
+        if depth_remaining == 0 {
+            return None;
+        }
+
+        let t = self
+            .transitions
+            .values()
+            .filter_map(|by_source| by_source.get(&q))
+            .flatten()
+            .max_by(|a, b| a.probability.cmp(&b.probability))?;
+
+        let mut children = Vec::with_capacity(t.target_states.len());
+        for &q_i in &t.target_states {
+            children.push(self.greedy_derivation(q_i, depth_remaining - 1)?);
+        }
+        Some(Derivation {
+            state: q,
+            symbol: self.t_integeriser.find_value(t.symbol).unwrap().clone(),
+            children,
+        })
+    }
+
+    /// Approximates the most probable tree via stochastic local search,
+    /// trading `most_probable_tree`'s exactness for tractability on the
+    /// larger synthetic automata `experiments::generate` can produce. Seeds
+    /// the search with `best_parse`'s single-run optimum, then for
+    /// `iterations` steps: picks a uniformly random node, re-expands it from
+    /// its state with a (possibly different) applicable transition and
+    /// greedily completes the resulting children, and accepts the edit
+    /// whenever it raises the tree's total probability (`probability`,
+    /// i.e. summed over all runs — not just the seeded one). After
+    /// `restart_every` steps in a row without an accepted edit, the search
+    /// restarts from a fresh `sample` draw to escape the local optimum.
+    /// Returns the best tree found, its probability, and the number of
+    /// candidates evaluated.
+    pub fn approximate_most_probable_tree<R: Rng>(
+        &self,
+        rng: &mut R,
+        iterations: usize,
+        restart_every: usize,
+        max_depth: usize,
+    ) -> (Tree<T>, LogDomain<f64>, usize) {
+        let (best_probabilities, best_derivations) = self.best_derivations();
+        let seed_state = best_probabilities
             .iter()
-            .zip(best_trees)
-            .max_by(|(&p_1, _), (p_2, _)| p_1.cmp(p_2))
-            .map(|(p, t)| (t.unwrap(), *p))
-            .unwrap()
+            .zip(&self.root_weights)
+            .map(|(&p, &root_p)| p * root_p)
+            .enumerate()
+            .max_by(|(_, p_1), (_, p_2)| p_1.cmp(p_2))
+            .map(|(q, _)| q)
+            .unwrap();
+
+        let mut current = best_derivations[seed_state].clone().unwrap();
+        let mut current_pr = self.probability(&current.to_tree());
+        let mut best_tree = current.to_tree();
+        let mut best_pr = current_pr;
+        let mut evaluated = 1;
+        let mut stagnant = 0;
+
+        for _ in 0..iterations {
+            if restart_every > 0 && stagnant >= restart_every {
+                let total_root_weight: LogDomain<f64> =
+                    self.root_weights.iter().cloned().sum();
+                let restart = Self::sample_categorical(
+                    rng,
+                    self.root_weights.iter().cloned().enumerate(),
+                    total_root_weight,
+                )
+                .and_then(|q| self.sample_derivation(rng, q, max_depth));
+                if let Some(d) = restart {
+                    current = d;
+                    current_pr = self.probability(&current.to_tree());
+                    evaluated += 1;
+                }
+                stagnant = 0;
+            }
+
+            let paths = current.paths();
+            let path = &paths[rng.gen_range(0, paths.len())];
+            let q = current.state_at(path);
+
+            let candidates: Vec<&Transition<usize, usize>> = self
+                .transitions
+                .values()
+                .filter_map(|by_source| by_source.get(&q))
+                .flatten()
+                .collect();
+            if candidates.is_empty() {
+                stagnant += 1;
+                continue;
+            }
+            let t = candidates[rng.gen_range(0, candidates.len())];
+
+            let children: Option<Vec<Derivation<T>>> = t
+                .target_states
+                .iter()
+                .map(|&q_i| self.greedy_derivation(q_i, max_depth))
+                .collect();
+            let children = match children {
+                Some(children) => children,
+                None => {
+                    stagnant += 1;
+                    continue;
+                }
+            };
+
+            let mut candidate = current.clone();
+            candidate.replace_at(
+                path,
+                Derivation {
+                    state: q,
+                    symbol: self
+                        .t_integeriser
+                        .find_value(t.symbol)
+                        .unwrap()
+                        .clone(),
+                    children,
+                },
+            );
+            let candidate_pr = self.probability(&candidate.to_tree());
+            evaluated += 1;
+
+            if candidate_pr > current_pr {
+                current = candidate;
+                current_pr = candidate_pr;
+                stagnant = 0;
+                if current_pr > best_pr {
+                    best_tree = current.to_tree();
+                    best_pr = current_pr;
+                }
+            } else {
+                stagnant += 1;
+            }
+        }
+
+        (best_tree, best_pr, evaluated)
     }
 }
 
@@ -488,11 +1915,86 @@ mod tests {
                           transition: 0 -> s(2, 2) # 0.1\n\
                           transition: 1 -> s(1, 2) # 0.3";
         let pta: PTA<usize, char> = pta_string.parse().unwrap();
-        let mpt = pta.most_probable_tree();
-        assert_eq!(mpt.0, "(s (a) (a))".parse().unwrap());
+        let mpt = pta.most_probable_tree(0).unwrap();
+        assert_eq!(mpt.0, "s( a, a )".parse().unwrap());
         assert_eq!(mpt.1, LogDomain::new(0.1807).unwrap());
     }
 
+    #[test]
+    fn test_k_best_trees_ranks_derivations() {
+        let pta_string = "root: 0 # 0.7\n\
+                          root: 1 # 0.2\n\
+                          root: 2 # 0.1\n\
+                          transition: 1 -> a() # 0.5\n\
+                          transition: 2 -> a() # 0.4\n\
+                          transition: 1 -> b() # 0.2\n\
+                          transition: 2 -> b() # 0.6\n\
+                          transition: 0 -> s(1, 1) # 0.9\n\
+                          transition: 0 -> s(2, 2) # 0.1\n\
+                          transition: 1 -> s(1, 2) # 0.3";
+        let pta: PTA<usize, char> = pta_string.parse().unwrap();
+
+        // best single derivation: root 0 -> s(1, 1) -> (1 -> a, 1 -> a),
+        // i.e. 0.7 * 0.9 * 0.5 * 0.5 = 0.1575 -- a single run's weight,
+        // not the tree's total probability summed over all its runs
+        // (which is what `most_probable_tree` would report for "s(a,a)")
+        let best = pta.k_best_trees(1);
+        assert_eq!(best.len(), 1);
+        assert_eq!(best[0].0, "s( a, a )".parse().unwrap());
+        assert_eq!(best[0].1, LogDomain::new(0.1575).unwrap());
+
+        let top_three = pta.k_best_trees(3);
+        assert_eq!(top_three.len(), 3);
+        assert_eq!(top_three[0], best[0]);
+        // non-increasing order of probability
+        assert!(top_three[0].1 >= top_three[1].1);
+        assert!(top_three[1].1 >= top_three[2].1);
+    }
+
+    #[test]
+    fn test_most_probable_tree_budgeted_proves_optimality() {
+        let pta_string = "root: 0 # 0.7\n\
+                          root: 1 # 0.2\n\
+                          root: 2 # 0.1\n\
+                          transition: 1 -> a() # 0.5\n\
+                          transition: 2 -> a() # 0.4\n\
+                          transition: 1 -> b() # 0.2\n\
+                          transition: 2 -> b() # 0.6\n\
+                          transition: 0 -> s(1, 1) # 0.9\n\
+                          transition: 0 -> s(2, 2) # 0.1\n\
+                          transition: 1 -> s(1, 2) # 0.3";
+        let pta: PTA<usize, char> = pta_string.parse().unwrap();
+        let (tree, pr, proven) = pta
+            .most_probable_tree_budgeted(Duration::from_secs(5), None)
+            .unwrap()
+            .unwrap();
+        assert_eq!(tree, "s( a, a )".parse().unwrap());
+        assert_eq!(pr, LogDomain::new(0.1807).unwrap());
+        assert!(proven);
+    }
+
+    #[test]
+    fn test_most_probable_tree_budgeted_expansion_cap() {
+        let pta_string = "root: 0 # 0.7\n\
+                          root: 1 # 0.2\n\
+                          root: 2 # 0.1\n\
+                          transition: 1 -> a() # 0.5\n\
+                          transition: 2 -> a() # 0.4\n\
+                          transition: 1 -> b() # 0.2\n\
+                          transition: 2 -> b() # 0.6\n\
+                          transition: 0 -> s(1, 1) # 0.9\n\
+                          transition: 0 -> s(2, 2) # 0.1\n\
+                          transition: 1 -> s(1, 2) # 0.3";
+        let pta: PTA<usize, char> = pta_string.parse().unwrap();
+        // the single allowed expansion pops the highest-potential seed
+        // `s`, which is still a prefix: the cap is hit before any
+        // complete tree is ever popped, so there is no candidate yet.
+        let result = pta
+            .most_probable_tree_budgeted(Duration::from_secs(5), Some(1))
+            .unwrap();
+        assert!(result.is_none());
+    }
+
     #[test]
     fn test_probability() {
         let pta_string = "root: 0 # 0.7\n\
@@ -506,10 +2008,10 @@ mod tests {
                           transition: 0 -> s(2, 2) # 0.1\n\
                           transition: 1 -> s(1, 2) # 0.3";
         let pta: PTA<usize, char> = pta_string.parse().unwrap();
-        let mut xi: Tree<char> = "(s (a) (b))".parse().unwrap();
+        let mut xi: Tree<char> = "s( a, b )".parse().unwrap();
         xi.is_prefix = true;
         assert_eq!(
-            pta.probability(&mut xi, &mut HashSet::new()),
+            pta.inside_probability(&mut xi, &mut HashSet::new()),
             LogDomain::new(0.0978).unwrap()
         );
     }
@@ -528,14 +2030,41 @@ mod tests {
                           transition: 0 -> s(2, 2) # 0.1\n\
                           transition: 1 -> s(1, 2) # 0.3";
         let pta: PTA<usize, char> = pta_string.parse().unwrap();
-        let mut xi: Tree<char> = "(s (a) (s))".parse().unwrap();
+        let mut xi: Tree<char> = "s( a, s )".parse().unwrap();
         xi.is_prefix = true;
         assert_eq!(
-            pta._potential_probability(&mut xi, &mut HashSet::new(),),
+            pta.potential_probability(&mut xi, &mut HashSet::new(),),
             LogDomain::new(0.0945).unwrap()
         );
     }
 
+    #[test]
+    fn test_best_run() {
+        let pta_string = "root: 0 # 0.7\n\
+                          root: 1 # 0.2\n\
+                          root: 2 # 0.1\n\
+                          transition: 1 -> a() # 0.5\n\
+                          transition: 2 -> a() # 0.4\n\
+                          transition: 1 -> b() # 0.2\n\
+                          transition: 2 -> b() # 0.6\n\
+                          transition: 0 -> s(1, 1) # 0.9\n\
+                          transition: 0 -> s(2, 2) # 0.1\n\
+                          transition: 1 -> s(1, 2) # 0.3";
+        let pta: PTA<usize, char> = pta_string.parse().unwrap();
+        let xi: Tree<char> = "s( a, b )".parse().unwrap();
+        let (pr, run) = pta.best_run(&xi);
+
+        // the summed `probability` (0.0978) mixes in less probable runs, so
+        // the single-best run's probability is strictly smaller
+        assert_eq!(pr, LogDomain::new(0.063).unwrap());
+        assert!(pr < pta.probability(&xi));
+        assert_eq!(run.to_tree(), xi);
+        assert_eq!(run.state, 0);
+        assert_eq!(run.probability, LogDomain::new(0.9).unwrap());
+        assert_eq!(run.children[0].state, 1);
+        assert_eq!(run.children[1].state, 1);
+    }
+
     #[test]
     fn test_best_parse() {
         let pta_string = "root: 0 # 0.7\n\
@@ -550,7 +2079,7 @@ mod tests {
                           transition: 1 -> s(1, 2) # 0.3";
         let pta: PTA<usize, char> = pta_string.parse().unwrap();
         let best_parse = pta.best_parse();
-        assert_eq!(best_parse.0, "(s (a) (a))".parse().unwrap());
+        assert_eq!(best_parse.0, "s( a, a )".parse().unwrap());
         assert_eq!(best_parse.1, LogDomain::new(0.1575).unwrap());
     }
 }
@@ -1,14 +1,13 @@
 use log_domain::LogDomain;
 use nom::simple_errors::Context;
 use nom::{
-    alt, char, do_parse, many0, many1, named, separated_nonempty_list, tag,
-    take_until_either, Err,
+    alt_complete, call, complete, do_parse, map_res, named, opt, recognize,
+    separated_nonempty_list, tag, take_while, take_while1, Err, IResult, Needed,
 };
-use std::collections::HashMap;
-use std::error::Error;
+use std::collections::{HashMap, VecDeque};
 use std::fmt;
 use std::hash::{Hash, Hasher};
-use std::str::FromStr;
+use std::str::{from_utf8, FromStr};
 
 /// A tree ξ ∈ T_Σ(X) for a ranked alphabet Σ and a set of variables X.
 #[derive(Debug, Eq, Clone)]
@@ -77,77 +76,110 @@ where
     /// Determines the height of the tree, i.e., the amount of nodes on the
     /// longest path from the root to a leaf.
     pub fn _get_height(&self) -> usize {
-        if self.children.is_empty() {
-            1
-        } else {
-            self.children
-                .iter()
-                .map(|t| t._get_height() + 1)
-                .max()
-                .unwrap()
-        }
+        self.iter_dfs().map(|(path, _)| path.len()).max().unwrap() + 1
     }
 
     /// Searches for the first variable in a breadth-first manner and replaces
     /// it with the given symbol σ. Returns true if the resulting tree remains a
     /// prefix (still contains variables) and false otherwise (ξ ∈ T_Σ).
     pub fn extend(&mut self, s: &A, sigma: &HashMap<A, usize>) -> bool {
-        let mut prefix = false;
-        let mut extended = false;
-        let mut xi_stack = Vec::new();
-
-        xi_stack.push(self);
-        while !xi_stack.is_empty() {
-            let xi = xi_stack.pop().unwrap();
-            // there is at least one direct child such that ξ(i) ∈ X
-            if xi.children.len() < *sigma.get(&xi.root).unwrap() {
-                // in case ξ already has been extended and another variable is
-                // found we know that ξ ∉ T_Σ
-                if extended {
-                    prefix = true;
-                    break;
-                } else {
-                    xi.children.push(Tree::new((*s).clone()));
-                    xi_stack.push(xi);
-                }
-                // only extend once
-                extended = true;
-            } else {
-                // look at all children
-                for xi_i in &mut xi.children {
-                    xi_stack.push(xi_i);
-                }
+        let underfull = |node: &Tree<A>| {
+            node.children.len() < *sigma.get(&node.root).unwrap()
+        };
+
+        let path = self
+            .iter_bfs()
+            .find(|(_, node)| underfull(node))
+            .map(|(path, _)| path);
+
+        match path {
+            Some(path) => {
+                let node = self.get_mut(&path).unwrap();
+                node.children.push(Tree::new((*s).clone()));
+                self.iter_dfs().any(|(_, node)| underfull(node))
             }
+            None => false,
         }
-        prefix
     }
 
-    /// Creates a tree from an S-expression.
-    /// (Credit to Felix Wittwer)
-    fn from_sexp(sexp: SExp) -> Tree<char> {
-        let mut content = Vec::new();
-        if let SExp::List(a) = sexp {
-            content = a.to_vec();
+    /// Returns an iterator yielding every node of the tree, paired with the
+    /// path of child indices (from the root, `[]` denoting the root itself)
+    /// that reaches it, in breadth-first order.
+    pub fn iter_bfs(&self) -> BfsIter<A> {
+        let mut queue = VecDeque::new();
+        queue.push_back((Vec::new(), self));
+        BfsIter { queue }
+    }
+
+    /// Returns an iterator yielding every node of the tree, paired with its
+    /// path, in pre-order depth-first order (a node before its children,
+    /// children left to right).
+    pub fn iter_dfs(&self) -> DfsIter<A> {
+        DfsIter {
+            stack: vec![(Vec::new(), self)],
         }
-        let mut children: Vec<Tree<char>> = Vec::new();
-        let mut root = 'a';
-        for sxp in content {
-            match sxp {
-                SExp::Atom(s) => root = s.chars().collect::<Vec<char>>()[0],
-                SExp::List(s) => {
-                    children.push(Tree::<char>::from_sexp(SExp::List(s)))
-                }
-            }
+    }
+
+    /// Resolves the node reached by descending `path` (a sequence of child
+    /// indices) from the root; `&[]` denotes the root itself. `None` if
+    /// `path` runs out of bounds anywhere along the way.
+    pub fn get(&self, path: &[usize]) -> Option<&Tree<A>> {
+        match path.split_first() {
+            Some((&i, rest)) => self.children.get(i)?.get(rest),
+            None => Some(self),
         }
-        Tree {
-            root,
-            children,
-            run: Vec::new(),
-            is_prefix: true,
+    }
+
+    /// Mutable counterpart of `get`.
+    pub fn get_mut(&mut self, path: &[usize]) -> Option<&mut Tree<A>> {
+        match path.split_first() {
+            Some((&i, rest)) => self.children.get_mut(i)?.get_mut(rest),
+            None => Some(self),
         }
     }
 }
 
+/// Iterator over a `Tree<A>`'s nodes in breadth-first order, built by
+/// `Tree::iter_bfs`.
+pub struct BfsIter<'a, A> {
+    queue: VecDeque<(Vec<usize>, &'a Tree<A>)>,
+}
+
+impl<'a, A> Iterator for BfsIter<'a, A> {
+    type Item = (Vec<usize>, &'a Tree<A>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (path, node) = self.queue.pop_front()?;
+        for (i, child) in node.children.iter().enumerate() {
+            let mut child_path = path.clone();
+            child_path.push(i);
+            self.queue.push_back((child_path, child));
+        }
+        Some((path, node))
+    }
+}
+
+/// Iterator over a `Tree<A>`'s nodes in pre-order depth-first order, built
+/// by `Tree::iter_dfs`.
+pub struct DfsIter<'a, A> {
+    stack: Vec<(Vec<usize>, &'a Tree<A>)>,
+}
+
+impl<'a, A> Iterator for DfsIter<'a, A> {
+    type Item = (Vec<usize>, &'a Tree<A>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (path, node) = self.stack.pop()?;
+        // push right-to-left so the leftmost child is popped (visited) first
+        for (i, child) in node.children.iter().enumerate().rev() {
+            let mut child_path = path.clone();
+            child_path.push(i);
+            self.stack.push((child_path, child));
+        }
+        Some((path, node))
+    }
+}
+
 /// Pretty print for trees.
 impl<A> fmt::Display for Tree<A>
 where
@@ -175,44 +207,73 @@ where
     }
 }
 
-#[derive(Debug, Eq, PartialEq, Clone)]
-pub enum SExp {
-    Atom(String),
-    List(Vec<SExp>),
+/// Parses the children of a node, i.e. a parenthesised, comma-separated
+/// list of trees: `( ξ1, ξ2, ... )` (cf. `Tree::fmt`).
+named!(parse_children<&[u8], Vec<Tree<char>>>,
+    do_parse!(
+           tag!("(")
+        >> take_while!(|b| b == b' ')
+        >> children: separated_nonempty_list!(
+            do_parse!(tag!(",") >> take_while!(|b| b == b' ') >> (())),
+            parse_tree
+        )
+        >> take_while!(|b| b == b' ')
+        >> tag!(")")
+        >> (children)
+    )
+);
+
+/// The byte length of the UTF-8 character starting at `lead_byte`, read off
+/// its leading bits (cf. the encoding table in the Unicode standard,
+/// sec. 3.9: a leading `0` means a 1-byte codepoint, `110` means 2 bytes,
+/// `1110` means 3 bytes, anything else (`1111`) means 4 bytes).
+fn utf8_char_len(lead_byte: u8) -> usize {
+    if lead_byte & 0b1000_0000 == 0 {
+        1
+    } else if lead_byte & 0b1110_0000 == 0b1100_0000 {
+        2
+    } else if lead_byte & 0b1111_0000 == 0b1110_0000 {
+        3
+    } else {
+        4
+    }
+}
+
+/// Parses a single UTF-8 *character*, unlike `take!(1)` which takes one
+/// *byte* and so panics or truncates on any multi-byte root symbol (e.g.
+/// the σ/γ/α/β alphabet the crate's own docs parse trees with).
+fn parse_one_char(input: &[u8]) -> IResult<&[u8], char> {
+    if input.is_empty() {
+        return Err(Err::Incomplete(Needed::Size(1)));
+    }
+    let len = utf8_char_len(input[0]);
+    if input.len() < len {
+        return Err(Err::Incomplete(Needed::Size(len - input.len())));
+    }
+    let c = from_utf8(&input[..len]).unwrap().chars().next().unwrap();
+    Ok((&input[len..], c))
 }
 
-/// Parse an S-expression.
-/// (Credit to Felix Wittwer)
-impl FromStr for SExp {
+/// Parses a tree in the exact grammar `Tree::fmt` produces: a single-
+/// character root symbol, optionally followed by `parse_children`.
+named!(parse_tree<&[u8], Tree<char>>,
+    do_parse!(
+           root: call!(parse_one_char)
+        >> children: opt!(complete!(do_parse!(
+            take_while!(|b| b == b' ') >> c: parse_children >> (c)
+        )))
+        >> (Tree::new_with_children(root, children.unwrap_or_default()))
+    )
+);
+
+/// Parses a tree printed by `Tree::fmt`, e.g. `s( a, b )`, so that
+/// `tree.to_string().parse::<Tree<char>>()` round-trips back to `tree`.
+impl FromStr for Tree<char> {
     type Err = String;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let input = s.as_bytes();
-
-        named!(list<&[u8],SExp>,
-            do_parse!(
-                   many0!(tag!(" "))
-                >> char!('(')
-                >> many0!(tag!(" "))
-                >> conts: separated_nonempty_list!(many1!(tag!(" ")), sxpr)
-                >> many0!(tag!(" "))
-                >> char!(')')
-
-                >> (SExp::List(conts))
-            )
-        );
-
-        named!(atom<&[u8],SExp>,
-            do_parse!(
-                   aa: take_until_either!(" )")
-                >> (SExp::Atom(String::from_utf8(aa.to_vec()).unwrap()))
-            )
-        );
-
-        named!(sxpr<&[u8],SExp>, alt!(list | atom));
-
-        match sxpr(input) {
-            Ok(ex) => Ok(ex.1),
+        match parse_tree(s.trim().as_bytes()) {
+            Ok((_, tree)) => Ok(tree),
             #[cold]
             Err(e) => {
                 match &e {
@@ -221,14 +282,10 @@ impl FromStr for SExp {
                          Incomplete Input Sequence!"
                     ),
                     Err::Error(ref rest) | Err::Failure(ref rest) => {
-                        eprintln!(
-                            "[Error] Could not parse input string due to \
-                             error: {}",
-                            e.description()
-                        );
                         let Context::Code(c, _) = rest;
                         eprintln!(
-                            "[Error] Next to parse was: {}",
+                            "[Error] Could not parse input string, next to \
+                             parse was: {}",
                             String::from_utf8(c.to_vec()).unwrap()
                         );
                     }
@@ -239,10 +296,153 @@ impl FromStr for SExp {
     }
 }
 
-impl FromStr for Tree<char> {
+/// Converts a `Tree<char>` into the equivalent `Tree<String>` by turning
+/// every (single-character) root symbol into a one-character `String`, so
+/// code that only ever dealt with `Tree<char>` keeps working against the
+/// more general `Tree<String>` parser below.
+impl From<Tree<char>> for Tree<String> {
+    fn from(xi: Tree<char>) -> Self {
+        Tree::new_with_children(
+            xi.root.to_string(),
+            xi.children.into_iter().map(Tree::from).collect(),
+        )
+    }
+}
+
+fn is_symbol_start(b: u8) -> bool {
+    (b as char).is_ascii_alphabetic() || b"!$%&*+-./:<=>?@^_~".contains(&b)
+}
+
+fn is_symbol_char(b: u8) -> bool {
+    is_symbol_start(b) || (b as char).is_ascii_digit()
+}
+
+fn is_digit(b: u8) -> bool {
+    (b as char).is_ascii_digit()
+}
+
+/// Parses a named symbol atom: `[A-Za-z!$%&*+\-./:<=>?@^_~][...]*`, i.e.
+/// the same character class a symbol may continue with, except that the
+/// first character may not be a digit (that is a numeric atom instead).
+named!(parse_symbol<&[u8], String>,
+    map_res!(take_while1!(is_symbol_char), |bytes: &[u8]| {
+        if is_digit(bytes[0]) {
+            Err("a symbol cannot start with a digit")
+        } else {
+            from_utf8(bytes).map(String::from).map_err(|_| "invalid utf8")
+        }
+    })
+);
+
+/// Parses a numeric atom: `[0-9]+(\.[0-9]+)?`.
+named!(parse_number<&[u8], String>,
+    map_res!(
+        recognize!(do_parse!(
+               take_while1!(is_digit)
+            >> opt!(complete!(do_parse!(
+                tag!(".") >> take_while1!(is_digit) >> (())
+            )))
+            >> (())
+        )),
+        |bytes: &[u8]| from_utf8(bytes).map(String::from)
+    )
+);
+
+/// Parses either kind of atom a node of a `Tree<String>` may carry: a
+/// named symbol (`NP`, `s`, `+`, ...) or a number (`42`, `3.14`).
+named!(parse_atom<&[u8], String>, alt_complete!(parse_number | parse_symbol));
+
+/// Parses the children of a node with `Tree<String>`'s multi-character
+/// atoms (cf. `parse_children`).
+named!(parse_children_s<&[u8], Vec<Tree<String>>>,
+    do_parse!(
+           tag!("(")
+        >> take_while!(|b| b == b' ')
+        >> children: separated_nonempty_list!(
+            do_parse!(tag!(",") >> take_while!(|b| b == b' ') >> (())),
+            parse_tree_s
+        )
+        >> take_while!(|b| b == b' ')
+        >> tag!(")")
+        >> (children)
+    )
+);
+
+/// Parses a tree whose atoms may be multi-character symbols or numbers
+/// (cf. `parse_tree`).
+named!(parse_tree_s<&[u8], Tree<String>>,
+    do_parse!(
+           root: parse_atom
+        >> children: opt!(complete!(do_parse!(
+            take_while!(|b| b == b' ') >> c: parse_children_s >> (c)
+        )))
+        >> (Tree::new_with_children(root, children.unwrap_or_default()))
+    )
+);
+
+/// Parses a tree like `NP( the, NP2( old, man ) )`: unlike `Tree<char>`,
+/// node labels may be arbitrary symbol or number atoms instead of a single
+/// character.
+impl FromStr for Tree<String> {
     type Err = String;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(Tree::<char>::from_sexp(s.parse()?))
+        match parse_tree_s(s.trim().as_bytes()) {
+            Ok((_, tree)) => Ok(tree),
+            #[cold]
+            Err(e) => {
+                match &e {
+                    Err::Incomplete(_) => eprintln!(
+                        "[Error] Parsing did not succeed: \
+                         Incomplete Input Sequence!"
+                    ),
+                    Err::Error(ref rest) | Err::Failure(ref rest) => {
+                        let Context::Code(c, _) = rest;
+                        eprintln!(
+                            "[Error] Could not parse input string, next to \
+                             parse was: {}",
+                            String::from_utf8(c.to_vec()).unwrap()
+                        );
+                    }
+                }
+                Err(e.to_string())
+            }
+        }
+    }
+}
+
+/// Parses `s` as a `Tree<String>` and additionally validates that every
+/// node's number of children matches its rank in `sigma`, returning the
+/// offending subtree together with a description on mismatch instead of
+/// silently accepting it (plain `FromStr` only checks that the input
+/// parses as *a* tree, not that it belongs to a particular alphabet).
+pub fn parse_checked(
+    s: &str,
+    sigma: &HashMap<String, usize>,
+) -> Result<Tree<String>, String> {
+    let tree: Tree<String> = s.parse()?;
+    check_arity(&tree, sigma)?;
+    Ok(tree)
+}
+
+fn check_arity(
+    xi: &Tree<String>,
+    sigma: &HashMap<String, usize>,
+) -> Result<(), String> {
+    match sigma.get(&xi.root) {
+        Some(&rank) if rank == xi.children.len() => {
+            xi.children.iter().try_for_each(|c| check_arity(c, sigma))
+        }
+        Some(&rank) => Err(format!(
+            "arity mismatch in `{}`: `{}` expects {} child(ren), found {}",
+            xi,
+            xi.root,
+            rank,
+            xi.children.len()
+        )),
+        None => Err(format!(
+            "arity mismatch in `{}`: `{}` is not part of the given alphabet",
+            xi, xi.root
+        )),
     }
 }